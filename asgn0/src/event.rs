@@ -101,12 +101,14 @@ pub fn on_event(
         },
         Event::MouseButtonDown {
             x, y, mouse_btn, ..
-        } => if mouse_btn == MouseButton::Left {
-            let (x, y) = transform_coords(x, y, display.window().size(), &*state);
-            if !(state.triangle && y < x) {
-                state.drag = SquarePart::from_coords(x, y);
+        } => {
+            if mouse_btn == MouseButton::Left {
+                let (x, y) = transform_coords(x, y, display.window().size(), &*state);
+                if !(state.triangle && y < x) {
+                    state.drag = SquarePart::from_coords(x, y);
+                }
             }
-        },
+        }
         Event::MouseButtonUp { mouse_btn, .. } if mouse_btn == MouseButton::Left => {
             state.drag = None;
         }
@@ -166,8 +168,9 @@ fn transform_coords(x: i32, y: i32, max: (u32, u32), state: &State) -> (f32, f32
             state.rotation.sin(),
             -state.rotation.sin(),
             state.rotation.cos(),
-        ].into_iter()
-            .cloned(),
+        ]
+        .into_iter()
+        .cloned(),
     );
     let off = RowVector2::from_iterator([state.offset.0, state.offset.1].into_iter().cloned());
     let xy = ((xy - off) / state.scale) * rot;
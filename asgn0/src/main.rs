@@ -29,7 +29,7 @@ fn main() {
 }
 
 fn on_loop(state: &mut State, _: &mut Sdl, display: &mut SDL2Facade) -> Result<bool, Error> {
-    let uniforms = uniform!{
+    let uniforms = uniform! {
         aspect_ratio: state.aspect_ratio,
         brightness: state.brightness,
         color: state.color,
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use noise::Distribution;
 use util::SampleMode;
 
 /// The parsed arguments.
@@ -39,6 +40,12 @@ pub enum Filter {
     /// Grayscale conversion.
     Grayscale,
 
+    /// Sets the amplitude of random noise.
+    NoiseAmplitude(f32),
+
+    /// Sets the distribution of random noise.
+    NoiseDistribution(Distribution),
+
     /// Writes out the current image.
     Output(PathBuf),
 
@@ -48,7 +55,11 @@ pub enum Filter {
     /// Changes the number of bits per channel.
     Quantitize(u8),
 
-    /// Adds random noise.
+    /// Quantitizes the image to the given number of bits per channel, after adding uniform noise
+    /// of up to 0.1. The noise distribution and amplitude options don't affect it.
+    RandomDither(u8),
+
+    /// Adds random noise, with the current distribution, amplitude, and seed.
     RandomNoise,
 
     /// Rotates the image counter-clockwise by the given angle.
@@ -63,6 +74,9 @@ pub enum Filter {
     /// Scales the image.
     Scale(f32, f32),
 
+    /// Seeds random noise.
+    Seed(u64),
+
     /// Sharpens an image by applying a gaussian blur and then extrapolating.
     Sharpen,
 }
@@ -125,6 +139,19 @@ pub fn parse<I: IntoIterator<Item = S>, S: AsRef<str>>(iter: I) -> Option<Args>
                 "-grayscale" => {
                     filters.push(Filter::Grayscale);
                 }
+                "-noise" => {
+                    let distribution = match iter.next()?.as_ref() {
+                        "gaussian" => Distribution::Gaussian,
+                        "salt-and-pepper" => Distribution::SaltAndPepper,
+                        "uniform" => Distribution::Uniform,
+                        _ => return None,
+                    };
+                    filters.push(Filter::NoiseDistribution(distribution));
+                }
+                "-noise-amplitude" => {
+                    let amplitude = iter.next()?.as_ref().parse().ok()?;
+                    filters.push(Filter::NoiseAmplitude(amplitude));
+                }
                 "-output" => {
                     let output: PathBuf = iter.next()?.as_ref().into();
                     filters.push(Filter::Output(output));
@@ -145,8 +172,7 @@ pub fn parse<I: IntoIterator<Item = S>, S: AsRef<str>>(iter: I) -> Option<Args>
                     if bits == 0 {
                         return None;
                     }
-                    filters.push(Filter::RandomNoise);
-                    filters.push(Filter::Quantitize(bits));
+                    filters.push(Filter::RandomDither(bits));
                 }
                 "-random-noise" => {
                     filters.push(Filter::RandomNoise);
@@ -173,6 +199,10 @@ pub fn parse<I: IntoIterator<Item = S>, S: AsRef<str>>(iter: I) -> Option<Args>
                     let y: f32 = iter.next()?.as_ref().parse().ok()?;
                     filters.push(Filter::Scale(x, y));
                 }
+                "-seed" => {
+                    let seed = iter.next()?.as_ref().parse().ok()?;
+                    filters.push(Filter::Seed(seed));
+                }
                 "-sharpen" => {
                     filters.push(Filter::Sharpen);
                 }
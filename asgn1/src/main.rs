@@ -7,6 +7,7 @@ extern crate log;
 mod args;
 mod blur;
 mod convolve;
+mod noise;
 mod pipe;
 mod rotate;
 mod scale;
@@ -24,6 +25,7 @@ use common::{
 };
 
 use args::Filter;
+use noise::{derive_seed, Distribution, DITHER_SALT, NOISE_SALT};
 
 use util::{polate, transform_as_yuv, Image, Pixel, SampleMode};
 
@@ -48,11 +50,16 @@ FILTERS:
                                     filter.
     -floyd-steinberg-dither BITS    Performs Floyd-Steinberg dithering.
     -grayscale                      Transforms the image to grayscale.
+    -noise gaussian                 Sets the distribution of random noise to Gaussian.
+    -noise salt-and-pepper          Sets the distribution of random noise to salt-and-pepper.
+    -noise uniform                  Sets the distribution of random noise to uniform.
+    -noise-amplitude F              Sets the amplitude of random noise. Defaults to 0.1. For
+                                    salt-and-pepper noise, this is the fraction of pixels changed.
     -output PATH                    Writes the current state of the image to the given path.
     -pipe COMMAND                   Pipes the image as a JPEG to the given command.
     -quantitize BITS                Quantitizes the image to have the given number of bits.
     -random-dither BITS             Quantitizes the image to have the given number of bits, with
-                                    random dithering.
+                                    random dithering. The noise options don't affect it.
     -random-noise                   Adds random noise.
     -rotate DEGS                    Rotates the image by the given angle.
     -sample bilinear                Sets sampling mode to bilinear.
@@ -60,6 +67,8 @@ FILTERS:
     -sample point                   Sets sampling mode to point.
     -saturation F                   Adjusts the saturation by the given factor.
     -scale X Y                      Scales the image by the given factor in the X and Y directions.
+    -seed N                         Seeds random noise, so that it's the same on every run. Each
+                                    kind of random filter gets its own noise from the seed.
     -sharpen                        Sharpens the image.
 
 NOTES:
@@ -73,6 +82,13 @@ NOTES:
     run_err(move || {
         let mut image: Image = open_image(args.input)?.to_rgba().into();
         let mut sample_mode = SampleMode::Point;
+        let mut noise_distribution = Distribution::Uniform;
+        let mut noise_amplitude = 0.1;
+        let mut seed: u64 = random();
+        // How many times each kind of random filter has been used since the seed was set, so
+        // that each use gets different noise.
+        let mut noise_uses = 0;
+        let mut dither_uses = 0;
 
         for filter in args.filters {
             debug!("Applying {:?}...", filter);
@@ -118,7 +134,8 @@ NOTES:
                         .flat_map(|y| {
                             let image = &image;
                             (0..w).into_par_iter().map(move |x| image[(x, y)])
-                        }).sum();
+                        })
+                        .sum();
                     let avg = sum * (w as f32 * h as f32).recip();
                     image = Image::from_fn(w, h, |x, y| polate(image[(x, y)], avg, 1.0 - f));
                 }
@@ -126,19 +143,23 @@ NOTES:
                     image = convolve::filter(
                         &image,
                         [[1.0, 0.0, -1.0], [2.0, 0.0, -2.0], [1.0, 0.0, -1.0]],
-                    ).normalize()
+                    )
+                    .normalize()
                         + convolve::filter(
                             &image,
                             [[1.0, 2.0, 1.0], [0.0, 0.0, 0.0], [-1.0, -2.0, -1.0]],
-                        ).normalize()
+                        )
+                        .normalize()
                         + convolve::filter(
                             &image,
                             [[-1.0, 0.0, 1.0], [-2.0, 0.0, 2.0], [-1.0, 0.0, 1.0]],
-                        ).normalize()
+                        )
+                        .normalize()
                         + convolve::filter(
                             &image,
                             [[-1.0, -2.0, -1.0], [0.0, 0.0, 0.0], [1.0, 2.0, 1.0]],
-                        ).normalize();
+                        )
+                        .normalize();
                     image *= 0.5;
                 }
                 Filter::FloydSteinbergDither(bits) => {
@@ -176,31 +197,26 @@ NOTES:
                         Pixel([l, l, l, a])
                     })
                 }
+                Filter::NoiseAmplitude(amplitude) => noise_amplitude = amplitude,
+                Filter::NoiseDistribution(distribution) => noise_distribution = distribution,
                 Filter::Output(path) => {
                     let image: RgbaImage = (&image).into();
                     image.save(path)?
                 }
                 Filter::Pipe(command) => pipe::filter(&image, command)?,
-                Filter::Quantitize(bits) => {
-                    let (w, h) = image.dims();
-                    let max = (bits as f32).exp2() - 1.0;
-                    image = Image::from_fn(w, h, |x, y| {
-                        let Pixel([r, g, b, a]) = image[(x, y)];
-                        let r = (r * max).round() / max;
-                        let g = (g * max).round() / max;
-                        let b = (b * max).round() / max;
-                        Pixel([r, g, b, a])
-                    })
+                Filter::Quantitize(bits) => image = quantitize(&image, bits),
+                Filter::RandomDither(bits) => {
+                    debug!("Using seed {}", seed);
+                    let seed = derive_seed(seed, DITHER_SALT, dither_uses);
+                    dither_uses += 1;
+                    image = noise::filter(&image, Distribution::Uniform, 0.1, seed);
+                    image = quantitize(&image, bits);
                 }
                 Filter::RandomNoise => {
-                    let (w, h) = image.dims();
-                    image = Image::from_fn(w, h, |x, y| {
-                        let Pixel([r, g, b, a]) = image[(x, y)];
-                        let rr: f32 = random();
-                        let rg: f32 = random();
-                        let rb: f32 = random();
-                        Pixel([r + rr * 0.1, g + rg * 0.1, b + rb * 0.1, a])
-                    })
+                    debug!("Using seed {}", seed);
+                    let seed = derive_seed(seed, NOISE_SALT, noise_uses);
+                    noise_uses += 1;
+                    image = noise::filter(&image, noise_distribution, noise_amplitude, seed);
                 }
                 Filter::Rotate(angle) => {
                     image = rotate::filter(&image, sample_mode, angle.to_radians())
@@ -213,6 +229,11 @@ NOTES:
                     })
                 }
                 Filter::Scale(x, y) => image = scale::filter(&image, sample_mode, x, y),
+                Filter::Seed(n) => {
+                    seed = n;
+                    noise_uses = 0;
+                    dither_uses = 0;
+                }
                 Filter::Sharpen => {
                     let (w, h) = image.dims();
                     let blur = blur::filter(&image);
@@ -225,3 +246,16 @@ NOTES:
         Ok(())
     })
 }
+
+/// Changes the number of bits per channel, rounding each channel to the nearest level.
+fn quantitize(image: &Image, bits: u8) -> Image {
+    let (w, h) = image.dims();
+    let max = (bits as f32).exp2() - 1.0;
+    Image::from_fn(w, h, |x, y| {
+        let Pixel([r, g, b, a]) = image[(x, y)];
+        let r = (r * max).round() / max;
+        let g = (g * max).round() / max;
+        let b = (b * max).round() / max;
+        Pixel([r, g, b, a])
+    })
+}
@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+use util::{Image, Pixel};
+
+/// The distribution random noise is drawn from.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Distribution {
+    /// Adds a value between 0 and the amplitude to each channel.
+    Uniform,
+
+    /// Adds a normally distributed value, with the amplitude as its standard deviation, to each
+    /// channel.
+    Gaussian,
+
+    /// Sets the given fraction of pixels to black or white.
+    SaltAndPepper,
+}
+
+/// The salt for `-random-noise`'s seeds.
+pub const NOISE_SALT: u64 = 0x6E6F_6973_6500_0000;
+
+/// The salt for `-random-dither`'s seeds.
+pub const DITHER_SALT: u64 = 0x6469_7468_6572_0000;
+
+/// Derives the seed for the `n`th use (counting from 0) of the kind of filter with the given salt.
+/// Each kind of filter has its own salt, so the noise it gets doesn't depend on which other
+/// filters are used, or in what order.
+pub fn derive_seed(seed: u64, salt: u64, n: u64) -> u64 {
+    mix(seed ^ salt).wrapping_add(n)
+}
+
+/// Adds random noise to an image. The noise for each pixel depends only on the seed and the
+/// pixel's position, so the output is the same however the work is split between threads.
+pub fn filter(image: &Image, distribution: Distribution, amplitude: f32, seed: u64) -> Image {
+    let (w, h) = image.dims();
+    Image::from_fn(w, h, |x, y| {
+        let mut rng = PixelRng::new(seed, x, y);
+        let Pixel([r, g, b, a]) = image[(x, y)];
+        match distribution {
+            Distribution::Uniform => {
+                let mut noise = || rng.next_f32() * amplitude;
+                Pixel([r + noise(), g + noise(), b + noise(), a])
+            }
+            Distribution::Gaussian => {
+                let mut noise = || rng.next_gaussian() * amplitude;
+                Pixel([r + noise(), g + noise(), b + noise(), a])
+            }
+            Distribution::SaltAndPepper => {
+                if rng.next_f32() < amplitude {
+                    let n = if rng.next_f32() < 0.5 { 0.0 } else { 1.0 };
+                    Pixel([n, n, n, a])
+                } else {
+                    Pixel([r, g, b, a])
+                }
+            }
+        }
+    })
+}
+
+/// A SplitMix64 random number generator for a single pixel.
+struct PixelRng(u64);
+
+impl PixelRng {
+    /// Creates a generator from a seed and a pixel's position.
+    fn new(seed: u64, x: u32, y: u32) -> PixelRng {
+        let pos = (u64::from(y) << 32) | u64::from(x);
+        PixelRng(mix(seed.wrapping_add(mix(pos))))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.0)
+    }
+
+    /// Returns a number uniformly distributed in [0, 1).
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns a normally distributed number, with a mean of 0 and a standard deviation of 1,
+    /// using the Box-Muller transform.
+    fn next_gaussian(&mut self) -> f32 {
+        let u1 = 1.0 - self.next_f32();
+        let u2 = self.next_f32();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+/// The SplitMix64 output function, which scrambles the bits of its input.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::rayon::ThreadPoolBuilder;
+
+    use noise::{derive_seed, filter, Distribution, DITHER_SALT, NOISE_SALT};
+    use util::{Image, Pixel};
+
+    #[test]
+    fn same_with_any_thread_count() {
+        let image = Image::from_fn(61, 37, |x, y| {
+            Pixel([x as f32 / 61.0, y as f32 / 37.0, 0.5, 1.0])
+        });
+        for &distribution in &[
+            Distribution::Uniform,
+            Distribution::Gaussian,
+            Distribution::SaltAndPepper,
+        ] {
+            let render = |threads| {
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap()
+                    .install(|| filter(&image, distribution, 0.25, 42))
+            };
+            let single = render(1);
+            assert_eq!(single, render(4));
+            assert_eq!(single, render(7));
+            assert!(single != filter(&image, distribution, 0.25, 43));
+        }
+    }
+
+    #[test]
+    fn derived_seeds_differ() {
+        let seeds = [
+            derive_seed(42, NOISE_SALT, 0),
+            derive_seed(42, NOISE_SALT, 1),
+            derive_seed(42, DITHER_SALT, 0),
+            derive_seed(43, NOISE_SALT, 0),
+        ];
+        for (i, a) in seeds.iter().enumerate() {
+            assert!(seeds[i + 1..].iter().all(|b| a != b));
+        }
+    }
+}
@@ -43,6 +43,7 @@ pub enum SampleMode {
 }
 
 /// A 2D image, where each pixel's value is an [f32; 4].
+#[derive(Debug, PartialEq)]
 pub struct Image {
     pixels: Vec<Pixel>,
     width: u32,
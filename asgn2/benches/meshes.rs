@@ -0,0 +1,125 @@
+//! Compares the memory used by, and the time taken to render, a large mesh
+//! stored as separate triangles and as a `Mesh`.
+//!
+//! Run with `cargo bench --bench meshes`. The number of quads along each side
+//! of the mesh, which has twice its square in triangles, can be given as an
+//! argument.
+
+extern crate cgmath;
+extern crate raytracer;
+
+use std::env;
+use std::mem::size_of;
+use std::sync::Arc;
+use std::time::Instant;
+
+use cgmath::Vector3;
+use raytracer::light::{Directional, DynamicLight};
+use raytracer::renderable::DynamicRenderable;
+use raytracer::util::{millis, WHITE};
+use raytracer::{Material, Mesh, Scene};
+
+fn main() {
+    let n = env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(100);
+    let mesh = grid(n);
+    let material = Material::default();
+
+    let base = Scene {
+        camera_position: Vector3::new(0.0, 3.0, -3.0),
+        camera_direction: Vector3::new(0.0, -1.0, 1.0) / 2.0f32.sqrt(),
+        camera_up: Vector3::new(0.0, 1.0, 1.0) / 2.0f32.sqrt(),
+        width: 64,
+        height: 48,
+        lights: vec![DynamicLight::Directional(Directional {
+            color: WHITE,
+            direction: Vector3::new(-1.0, -2.0, 1.0),
+            intensity: 1.0,
+        })],
+        ..Scene::default()
+    };
+    let triangles = Scene {
+        objects: mesh
+            .triangles(material)
+            .into_iter()
+            .map(DynamicRenderable::Triangle)
+            .collect(),
+        ..base.clone()
+    };
+    let meshes = Scene {
+        meshes: vec![mesh],
+        materials: vec![material],
+        ..base
+    };
+
+    let faces = meshes.meshes[0].faces.len();
+    println!(
+        "{} triangles, {} vertices",
+        faces,
+        meshes.meshes[0].positions.len()
+    );
+    let triangles_size = triangles.objects.capacity() * size_of::<DynamicRenderable>();
+    let mesh_size = mesh_size(&meshes.meshes[0]) + size_of::<Material>();
+    println!(
+        "separate triangles: {:>6.1} bytes per triangle",
+        triangles_size as f64 / faces as f64
+    );
+    println!(
+        "mesh:               {:>6.1} bytes per triangle ({:.1}x less)",
+        mesh_size as f64 / faces as f64,
+        triangles_size as f64 / mesh_size as f64
+    );
+
+    let image = time("separate triangles", &triangles);
+    assert_eq!(time("mesh", &meshes), image);
+}
+
+/// Returns a mesh of a wavy square, divided into `n` by `n` quads.
+fn grid(n: u32) -> Mesh {
+    let (mut positions, mut faces) = (Vec::new(), Vec::new());
+    for i in 0..=n {
+        for j in 0..=n {
+            let (x, z) = (
+                i as f32 / n as f32 * 4.0 - 2.0,
+                j as f32 / n as f32 * 4.0 - 2.0,
+            );
+            let y = 0.2 * (3.0 * x).sin() * (3.0 * z).cos();
+            positions.push(Vector3::new(x, y, z));
+        }
+    }
+    let index = |i: u32, j: u32| i * (n + 1) + j;
+    for i in 0..n {
+        for j in 0..n {
+            let (a, b) = (index(i, j), index(i + 1, j));
+            let (c, d) = (index(i + 1, j + 1), index(i, j + 1));
+            faces.push([a, b, c]);
+            faces.push([a, c, d]);
+        }
+    }
+    Mesh {
+        positions: Arc::new(positions),
+        faces: Arc::new(faces),
+        ..Mesh::default()
+    }
+}
+
+/// Returns the number of bytes a mesh's buffers take up.
+fn mesh_size(mesh: &Mesh) -> usize {
+    size_of::<Mesh>()
+        + mesh.positions.capacity() * size_of::<Vector3<f32>>()
+        + mesh.faces.capacity() * size_of::<[u32; 3]>()
+}
+
+/// Renders a scene, printing how long it took.
+fn time(name: &str, scene: &Scene) -> Vec<[f32; 4]> {
+    let start = Instant::now();
+    let image = scene.render();
+    println!(
+        "{:<19} {:>6} ms to render",
+        format!("{}:", name),
+        millis(start.elapsed())
+    );
+    image.pixels().to_vec()
+}
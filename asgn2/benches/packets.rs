@@ -0,0 +1,44 @@
+//! Compares how quickly each example scene renders with its camera rays traced
+//! in packets, as `Scene::render` does, and one at a time.
+//!
+//! Run with `cargo bench --bench packets --features scalar`.
+
+#[macro_use]
+extern crate criterion;
+extern crate raytracer;
+
+use std::fs;
+
+use criterion::{Criterion, Throughput};
+use raytracer::Scene;
+
+/// The width scenes are rendered at, as in the golden tests. The height is
+/// scaled to keep the scene's aspect ratio.
+const WIDTH: u32 = 80;
+
+fn render(c: &mut Criterion) {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes");
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "scn"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        let mut scene = Scene::from_file(&path).unwrap();
+        scene.height = (scene.height * WIDTH / scene.width).max(1);
+        scene.width = WIDTH;
+
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Elements(u64::from(scene.width * scene.height)));
+        group.sample_size(10);
+        group.bench_function("packets", |b| b.iter(|| scene.render()));
+        group.bench_function("scalar", |b| b.iter(|| scene.render_scalar()));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
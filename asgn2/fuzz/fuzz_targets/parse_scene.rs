@@ -0,0 +1,12 @@
+//! Checks that the scene parser never panics, whatever its input.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate raytracer;
+
+use raytracer::Scene;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Scene::from_reader(data);
+});
@@ -0,0 +1,742 @@
+//! Keyframed animation of scenes.
+//!
+//! An animation is described in a sidecar file next to the scene, in one of
+//! the structured `Format`s. It has tracks for the camera, for lights, and
+//! for groups of objects and meshes, each a list of keyframes at (possibly
+//! fractional) frame numbers. A keyframe may set any subset of its track's
+//! properties; each property is interpolated between the keyframes that set
+//! it, and holds its first or last value outside of them. Properties no
+//! keyframe sets keep their value from the scene.
+//!
+//! Camera paths flown in the GUI can be recorded as animations, with a
+//! keyframe for each frame the GUI rendered.
+//!
+//! For example, in TOML:
+//!
+//! ```toml
+//! [camera]
+//! interpolation = "spline"
+//! keyframes = [
+//!     { frame = 0, position = [0, 0, -5] },
+//!     { frame = 60, position = [5, 0, 0] },
+//!     { frame = 120, position = [0, 0, 5], direction = [0, 0, -1] },
+//! ]
+//!
+//! [[lights]]
+//! light = 0
+//! keyframes = [{ frame = 0, intensity = 0 }, { frame = 30, intensity = 1 }]
+//!
+//! [[objects]]
+//! first = 2
+//! count = 3
+//! pivot = [0, 0, 3]
+//! keyframes = [{ frame = 0, rotation = [0, 0, 0] }, { frame = 120, rotation = [0, 360, 0] }]
+//! ```
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write};
+use std::ops::{Add, Mul, Range, Sub};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use cgmath::{Deg, Euler, InnerSpace, One, Quaternion, Rotation, Vector3};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use framebuffer::Framebuffer;
+use light::DynamicLight;
+use mesh::Mesh;
+use parser::{self, Format, ParseError, ParseErrors};
+use renderable::DynamicRenderable;
+use scene::Scene;
+use util::rgb_from_vector;
+
+/// The error for an animation file that isn't in a structured format.
+const NOT_STRUCTURED: &str = "animations must be JSON, TOML, or YAML files";
+
+/// How a property is interpolated between keyframes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Straight lines between keyframes.
+    #[default]
+    Linear,
+
+    /// A Catmull-Rom spline through the keyframes, which passes through each
+    /// of them smoothly.
+    Spline,
+}
+
+/// An animation of a scene.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Animation {
+    /// The keyframes for the camera.
+    pub camera: Track<CameraKey>,
+
+    /// The keyframes for lights.
+    pub lights: Vec<LightTrack>,
+
+    /// The keyframes for groups of objects and meshes.
+    pub objects: Vec<ObjectTrack>,
+}
+
+/// A sequence of keyframes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Track<K> {
+    /// How properties are interpolated between keyframes.
+    pub interpolation: Interpolation,
+
+    /// The keyframes, in any order.
+    pub keyframes: Vec<K>,
+}
+
+impl<K> Default for Track<K> {
+    fn default() -> Track<K> {
+        Track {
+            interpolation: Interpolation::default(),
+            keyframes: Vec::new(),
+        }
+    }
+}
+
+/// A keyframe for the camera.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraKey {
+    /// The frame the keyframe is at.
+    pub frame: f32,
+
+    /// The position of the camera.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 3]>,
+
+    /// The direction the camera is facing. Needn't be normalized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<[f32; 3]>,
+
+    /// The camera's up vector. Needn't be normalized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up: Option<[f32; 3]>,
+
+    /// One half of the vertical angle of the view frustrum, in degrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub half_angle: Option<f32>,
+}
+
+impl CameraKey {
+    /// Creates a keyframe with the position, direction, and up vector of a
+    /// scene's camera.
+    pub fn from_scene(frame: f32, scene: &Scene) -> CameraKey {
+        CameraKey {
+            frame,
+            position: Some(scene.camera_position.into()),
+            direction: Some(scene.camera_direction.into()),
+            up: Some(scene.camera_up.into()),
+            half_angle: None,
+        }
+    }
+}
+
+/// The keyframes for a light.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightTrack {
+    /// The index of the light in the scene.
+    pub light: usize,
+
+    /// How properties are interpolated between keyframes.
+    pub interpolation: Interpolation,
+
+    /// The keyframes, in any order.
+    pub keyframes: Vec<LightKey>,
+}
+
+/// A keyframe for a light. Properties the light doesn't have are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightKey {
+    /// The frame the keyframe is at.
+    pub frame: f32,
+
+    /// The color of the light.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 3]>,
+
+    /// The position of a point or spot light.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 3]>,
+
+    /// The direction of a directional or spot light.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<[f32; 3]>,
+
+    /// The intensity of the light.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intensity: Option<f32>,
+}
+
+/// The keyframes for a group of objects, which are transformed together.
+/// Meshes are numbered after the scene's objects, so that the mesh at index
+/// `i` in `meshes` is object `objects.len() + i`, and is moved as a whole.
+/// That includes each run of triangles from a scene file, which is one mesh.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectTrack {
+    /// The index of the first object in the scene.
+    pub first: usize,
+
+    /// The number of objects.
+    pub count: usize,
+
+    /// The point the objects are rotated and scaled about.
+    pub pivot: [f32; 3],
+
+    /// How properties are interpolated between keyframes.
+    pub interpolation: Interpolation,
+
+    /// The keyframes, in any order.
+    pub keyframes: Vec<ObjectKey>,
+}
+
+impl Default for ObjectTrack {
+    fn default() -> ObjectTrack {
+        ObjectTrack {
+            first: 0,
+            count: 1,
+            pivot: [0.0, 0.0, 0.0],
+            interpolation: Interpolation::default(),
+            keyframes: Vec::new(),
+        }
+    }
+}
+
+/// A keyframe for a group of objects. The transform is relative to the
+/// objects' positions in the scene: they're scaled, then rotated, about the
+/// pivot, then translated.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectKey {
+    /// The frame the keyframe is at.
+    pub frame: f32,
+
+    /// The offset to move the objects by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<[f32; 3]>,
+
+    /// The rotation about the x, y, and z axes, in degrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[f32; 3]>,
+
+    /// The uniform scale factor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f32>,
+}
+
+impl Animation {
+    /// Reads an animation from a file, which must be in one of the structured
+    /// `Format`s.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Animation, ParseErrors> {
+        let path = path.as_ref();
+        match Format::from_path(path) {
+            Some(format) => parser::read_structured(path, format, Animation::from_structured),
+            None => {
+                let err = IoError::new(ErrorKind::InvalidInput, NOT_STRUCTURED);
+                Err(ParseErrors(vec![ParseError::io(path, err)]))
+            }
+        }
+    }
+
+    /// Parses an animation from a structured description.
+    pub fn from_structured(input: &str, format: Format) -> Result<Animation, ParseErrors> {
+        parser::deserialize(input, format)
+    }
+
+    /// Writes the animation to a file, which must have the extension of one of
+    /// the structured `Format`s.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> IoResult<()> {
+        let path = path.as_ref();
+        let format = Format::from_path(path)
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, NOT_STRUCTURED))?;
+        let mut f = BufWriter::new(File::create(path)?);
+        f.write_all(self.to_structured(format)?.as_bytes())?;
+        f.flush()
+    }
+
+    /// Writes the animation as a structured description.
+    pub fn to_structured(&self, format: Format) -> Result<String, IoError> {
+        parser::serialize(self, format)
+    }
+
+    /// Returns the range of frames from 0 to the last keyframe, inclusive.
+    pub fn frames(&self) -> Range<u32> {
+        let camera = self.camera.keyframes.iter().map(Keyframe::frame);
+        let lights = self
+            .lights
+            .iter()
+            .flat_map(|t| t.keyframes.iter().map(Keyframe::frame));
+        let objects = self
+            .objects
+            .iter()
+            .flat_map(|t| t.keyframes.iter().map(Keyframe::frame));
+        let last = camera.chain(lights).chain(objects).fold(0.0, f32::max);
+        0..last.ceil() as u32 + 1
+    }
+
+    /// Checks that every track refers to lights, objects and meshes in the
+    /// scene.
+    pub fn check(&self, scene: &Scene) -> Result<(), String> {
+        for (i, track) in self.lights.iter().enumerate() {
+            if track.light >= scene.lights.len() {
+                return Err(format!(
+                    "light track {} animates light {}, but the scene has {} lights",
+                    i,
+                    track.light,
+                    scene.lights.len()
+                ));
+            }
+        }
+        let objects = scene.objects.len() + scene.meshes.len();
+        for (i, track) in self.objects.iter().enumerate() {
+            if track.first + track.count > objects {
+                return Err(format!(
+                    "object track {} animates objects {}..{}, but the scene has {} objects \
+                     and meshes",
+                    i,
+                    track.first,
+                    track.first + track.count,
+                    objects
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the scene as it is at the given frame. Tracks referring to
+    /// lights, objects or meshes that aren't in the scene are ignored.
+    pub fn apply(&self, scene: &Scene, frame: f32) -> Scene {
+        let mut scene = scene.clone();
+
+        let camera = &self.camera;
+        let sample_vector = |get: fn(&CameraKey) -> Option<[f32; 3]>| {
+            sample(&camera.keyframes, frame, camera.interpolation, |k| {
+                get(k).map(Vector3::from)
+            })
+        };
+        if let Some(position) = sample_vector(|k| k.position) {
+            scene.camera_position = position;
+        }
+        if let Some(direction) = sample_vector(|k| k.direction) {
+            scene.camera_direction = direction.normalize();
+        }
+        if let Some(up) = sample_vector(|k| k.up) {
+            scene.camera_up = up.normalize();
+        }
+        let half_angle = sample(&camera.keyframes, frame, camera.interpolation, |k| {
+            k.half_angle
+        });
+        if let Some(half_angle) = half_angle {
+            scene.camera_half_angle_tan = half_angle.to_radians().tan();
+        }
+
+        for track in &self.lights {
+            if let Some(light) = scene.lights.get_mut(track.light) {
+                track.apply(light, frame);
+            }
+        }
+
+        let objects = scene.objects.len();
+        for track in &self.objects {
+            let end = (track.first + track.count).min(objects + scene.meshes.len());
+            let first = track.first.min(end);
+            let meshes = first.saturating_sub(objects)..end.saturating_sub(objects);
+            track.apply(
+                &mut scene.objects[first.min(objects)..end.min(objects)],
+                &mut scene.meshes[meshes],
+                frame,
+            );
+        }
+
+        scene
+    }
+
+    /// Renders each of the given frames, passing each image to `f` along with
+    /// its frame number. With the `rayon` feature, frames are rendered in
+    /// parallel, so `f` may be called in any order.
+    #[cfg(feature = "rayon")]
+    pub fn render_frames<F>(&self, scene: &Scene, frames: Range<u32>, f: F)
+    where
+        F: Fn(u32, Framebuffer) + Sync,
+    {
+        frames
+            .into_par_iter()
+            .for_each(|frame| f(frame, self.apply(scene, frame as f32).render()));
+    }
+
+    /// Renders each of the given frames, passing each image to `f` along with
+    /// its frame number.
+    #[cfg(not(feature = "rayon"))]
+    pub fn render_frames<F>(&self, scene: &Scene, frames: Range<u32>, f: F)
+    where
+        F: Fn(u32, Framebuffer),
+    {
+        for frame in frames {
+            f(frame, self.apply(scene, frame as f32).render());
+        }
+    }
+}
+
+impl LightTrack {
+    /// Sets the properties of a light to their values at the given frame.
+    fn apply(&self, light: &mut DynamicLight, frame: f32) {
+        let sample_vector = |get: fn(&LightKey) -> Option<[f32; 3]>| {
+            sample(&self.keyframes, frame, self.interpolation, |k| {
+                get(k).map(Vector3::from)
+            })
+        };
+        let color = sample_vector(|k| k.color).map(rgb_from_vector);
+        let position = sample_vector(|k| k.position);
+        let direction = sample_vector(|k| k.direction);
+        let intensity = sample(&self.keyframes, frame, self.interpolation, |k| k.intensity);
+
+        let (c, p, d, i) = match *light {
+            DynamicLight::Directional(ref mut l) => {
+                (&mut l.color, None, Some(&mut l.direction), &mut l.intensity)
+            }
+            DynamicLight::Point(ref mut l) => {
+                (&mut l.color, Some(&mut l.position), None, &mut l.intensity)
+            }
+            DynamicLight::Spot(ref mut l) => (
+                &mut l.color,
+                Some(&mut l.position),
+                Some(&mut l.direction),
+                &mut l.intensity,
+            ),
+        };
+        if let Some(color) = color {
+            *c = color;
+        }
+        if let (Some(p), Some(position)) = (p, position) {
+            *p = position;
+        }
+        if let (Some(d), Some(direction)) = (d, direction) {
+            *d = direction;
+        }
+        if let Some(intensity) = intensity {
+            *i = intensity;
+        }
+    }
+}
+
+impl ObjectTrack {
+    /// Transforms the objects and meshes of the track to where they are at
+    /// the given frame.
+    fn apply(&self, objects: &mut [DynamicRenderable], meshes: &mut [Mesh], frame: f32) {
+        let sample_vector = |get: fn(&ObjectKey) -> Option<[f32; 3]>| {
+            sample(&self.keyframes, frame, self.interpolation, |k| {
+                get(k).map(Vector3::from)
+            })
+        };
+        let translation = sample_vector(|k| k.translation).unwrap_or(Vector3::new(0.0, 0.0, 0.0));
+        let rotation = sample_vector(|k| k.rotation).map_or_else(Quaternion::one, |r| {
+            Quaternion::from(Euler::new(Deg(r.x), Deg(r.y), Deg(r.z)))
+        });
+        let scale = sample(&self.keyframes, frame, self.interpolation, |k| k.scale).unwrap_or(1.0);
+
+        let pivot = Vector3::from(self.pivot);
+        let point =
+            |p: Vector3<f32>| pivot + rotation.rotate_vector((p - pivot) * scale) + translation;
+        for obj in objects {
+            match *obj {
+                DynamicRenderable::Plane(ref mut p) => {
+                    p.point = point(p.point);
+                    p.normal = rotation.rotate_vector(p.normal);
+                }
+                DynamicRenderable::Sphere(ref mut s) => {
+                    s.position = point(s.position);
+                    s.radius *= scale;
+                }
+                DynamicRenderable::Triangle(ref mut t) => {
+                    let (v1, v2, v3) = t.vertices;
+                    t.vertices = (point(v1), point(v2), point(v3));
+                    t.normal = rotation.rotate_vector(t.normal);
+                }
+            }
+        }
+        for mesh in meshes {
+            for p in Arc::make_mut(&mut mesh.positions) {
+                *p = point(*p);
+            }
+            if let Some(ref mut normals) = mesh.normals {
+                for n in Arc::make_mut(normals) {
+                    *n = rotation.rotate_vector(*n);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the path of the image for a frame. A run of `#`s in the file name
+/// is replaced with the frame number, padded with zeroes to the length of the
+/// run; otherwise, the frame number is added to the end of the file stem,
+/// padded to four digits.
+pub fn frame_path<P: AsRef<Path>>(path: P, frame: u32) -> PathBuf {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    let name = match name.find('#') {
+        Some(start) => {
+            let len = name[start..].len() - name[start..].trim_start_matches('#').len();
+            format!(
+                "{}{:0width$}{}",
+                &name[..start],
+                frame,
+                &name[start + len..],
+                width = len
+            )
+        }
+        None => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            match path.extension() {
+                Some(ext) => format!("{}_{:04}.{}", stem, frame, ext.to_string_lossy()),
+                None => format!("{}_{:04}", stem, frame),
+            }
+        }
+    };
+    path.with_file_name(name)
+}
+
+/// Values that can be interpolated.
+trait Lerp: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> {}
+
+impl<T> Lerp for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> {}
+
+/// Samples a property at the given frame, from the keyframes `get` returns a
+/// value for. Returns `None` if no keyframe sets the property.
+fn sample<K, T, F>(keyframes: &[K], frame: f32, interpolation: Interpolation, get: F) -> Option<T>
+where
+    K: Keyframe,
+    T: Lerp,
+    F: Fn(&K) -> Option<T>,
+{
+    let mut points = keyframes
+        .iter()
+        .filter_map(|k| get(k).map(|v| (k.frame(), v)))
+        .collect::<Vec<_>>();
+    points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+    let last = points.len().checked_sub(1)?;
+    if frame <= points[0].0 {
+        return Some(points[0].1);
+    } else if frame >= points[last].0 {
+        return Some(points[last].1);
+    }
+    let i = points.iter().rposition(|&(f, _)| f <= frame).unwrap_or(0);
+    let ((f1, p1), (f2, p2)) = (points[i], points[i + 1]);
+    let t = (frame - f1) / (f2 - f1);
+    Some(match interpolation {
+        Interpolation::Linear => p1 + (p2 - p1) * t,
+        Interpolation::Spline => {
+            let p0 = if i > 0 { points[i - 1].1 } else { p1 };
+            let p3 = points.get(i + 2).map_or(p2, |p| p.1);
+            let (t2, t3) = (t * t, t * t * t);
+            (p1 * 2.0
+                + (p2 - p0) * t
+                + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+                + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+                * 0.5
+        }
+    })
+}
+
+/// A keyframe of any track.
+trait Keyframe {
+    /// Returns the frame the keyframe is at.
+    fn frame(&self) -> f32;
+}
+
+impl Keyframe for CameraKey {
+    fn frame(&self) -> f32 {
+        self.frame
+    }
+}
+
+impl Keyframe for LightKey {
+    fn frame(&self) -> f32 {
+        self.frame
+    }
+}
+
+impl Keyframe for ObjectKey {
+    fn frame(&self) -> f32 {
+        self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use cgmath::{InnerSpace, Vector3};
+
+    use animation::{frame_path, Animation, CameraKey};
+    use light::{DynamicLight, Point};
+    use mesh::Mesh;
+    use parser::Format;
+    use renderable::{DynamicRenderable, Sphere};
+    use scene::Scene;
+    use util::BLACK;
+
+    #[test]
+    fn apply() {
+        let animation = Animation::from_structured(
+            r#"
+            [camera]
+            keyframes = [
+                { frame = 0, position = [0, 0, 0] },
+                { frame = 10, position = [10, 0, 0], direction = [1, 0, 0] },
+            ]
+
+            [[lights]]
+            light = 0
+            interpolation = "spline"
+            keyframes = [
+                { frame = 0, intensity = 0 },
+                { frame = 10, intensity = 1 },
+                { frame = 20, intensity = 2 },
+                { frame = 30, intensity = 3 },
+            ]
+
+            [[objects]]
+            pivot = [0, 0, 1]
+            keyframes = [
+                { frame = 0, rotation = [0, 0, 0], scale = 1 },
+                { frame = 10, rotation = [0, 180, 0], scale = 2 },
+            ]
+            "#,
+            Format::Toml,
+        )
+        .unwrap();
+        let base = Scene {
+            lights: vec![DynamicLight::Point(Point {
+                color: BLACK,
+                position: Vector3::new(0.0, 0.0, 0.0),
+                intensity: 5.0,
+                attenuation: Default::default(),
+            })],
+            objects: vec![DynamicRenderable::Sphere(Sphere {
+                position: Vector3::new(0.0, 0.0, 2.0),
+                radius: 1.0,
+                material: Default::default(),
+            })],
+            ..Scene::default()
+        };
+        assert_eq!(animation.check(&base), Ok(()));
+
+        let close = |a: Vector3<f32>, b: Vector3<f32>| (a - b).magnitude() < 1e-4;
+        let scene = animation.apply(&base, 5.0);
+        assert!(close(scene.camera_position, Vector3::new(5.0, 0.0, 0.0)));
+        assert!(close(scene.camera_direction, Vector3::new(1.0, 0.0, 0.0)));
+        match scene.objects[0] {
+            DynamicRenderable::Sphere(ref s) => {
+                let expected = Vector3::new(1.5, 0.0, 1.0);
+                assert!(close(s.position, expected), "{:?}", s.position);
+                assert!((s.radius - 1.5).abs() < 1e-4);
+            }
+            ref o => panic!("unexpected object {:?}", o),
+        }
+
+        let scene = animation.apply(&base, -5.0);
+        assert_eq!(scene.camera_position, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(scene.camera_direction, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(scene.objects, base.objects);
+
+        // With evenly spaced keyframes along a line, the spline is the line.
+        match animation.apply(&base, 15.0).lights[0] {
+            DynamicLight::Point(ref l) => assert!((l.intensity - 1.5).abs() < 1e-4),
+            ref l => panic!("unexpected light {:?}", l),
+        }
+
+        assert!(animation.check(&Scene::default()).is_err());
+    }
+
+    #[test]
+    fn meshes() {
+        // The track covers the sphere, numbered first, and the mesh after it.
+        let animation = Animation::from_structured(
+            r#"
+            [[objects]]
+            count = 2
+            keyframes = [{ frame = 0 }, { frame = 10, translation = [0, 2, 0], rotation = [0, 0, 90] }]
+            "#,
+            Format::Toml,
+        )
+        .unwrap();
+        let v = Vector3::new;
+        let base = Scene {
+            objects: vec![DynamicRenderable::Sphere(Sphere {
+                position: v(0.0, 0.0, 0.0),
+                radius: 1.0,
+                material: Default::default(),
+            })],
+            meshes: vec![Mesh {
+                positions: Arc::new(vec![v(1.0, 0.0, 0.0), v(2.0, 0.0, 0.0), v(1.0, 1.0, 0.0)]),
+                normals: Some(Arc::new(vec![v(1.0, 0.0, 0.0); 3])),
+                faces: Arc::new(vec![[0, 1, 2]]),
+                ..Mesh::default()
+            }],
+            materials: vec![Default::default()],
+            ..Scene::default()
+        };
+        assert_eq!(animation.check(&base), Ok(()));
+        let only_objects = Scene {
+            meshes: Vec::new(),
+            ..base.clone()
+        };
+        assert!(animation.check(&only_objects).is_err());
+
+        let close = |a: Vector3<f32>, b: Vector3<f32>| (a - b).magnitude() < 1e-4;
+        let scene = animation.apply(&base, 10.0);
+        let mesh = &scene.meshes[0];
+        assert!(close(mesh.positions[0], v(0.0, 3.0, 0.0)));
+        assert!(close(mesh.positions[1], v(0.0, 4.0, 0.0)));
+        assert!(close(mesh.positions[2], v(-1.0, 3.0, 0.0)));
+        assert!(close(mesh.normals.as_ref().unwrap()[0], v(0.0, 1.0, 0.0)));
+        match scene.objects[0] {
+            DynamicRenderable::Sphere(ref s) => assert!(close(s.position, v(0.0, 2.0, 0.0))),
+            ref o => panic!("unexpected object {:?}", o),
+        }
+        // The scene it was applied to keeps its geometry.
+        assert_eq!(base.meshes[0].positions[0], v(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn record() {
+        let mut scene = Scene::default();
+        let mut animation = Animation::default();
+        for i in 0..3 {
+            scene.camera_position = Vector3::new(i as f32, 0.0, 0.0);
+            let key = CameraKey::from_scene(i as f32, &scene);
+            animation.camera.keyframes.push(key);
+        }
+        assert_eq!(animation.frames(), 0..3);
+
+        let toml = animation.to_structured(Format::Toml).unwrap();
+        let animation = Animation::from_structured(&toml, Format::Toml).unwrap();
+        let scene = animation.apply(&Scene::default(), 1.0);
+        assert_eq!(scene.camera_position, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(Animation::default().frames(), 0..1);
+    }
+
+    #[test]
+    fn frame_paths() {
+        assert_eq!(frame_path("out/a.png", 7), Path::new("out/a_0007.png"));
+        assert_eq!(frame_path("a", 12345), Path::new("a_12345"));
+        assert_eq!(frame_path("a-###.png", 7), Path::new("a-007.png"));
+        assert_eq!(frame_path("a#.b#.png", 42), Path::new("a42.b#.png"));
+    }
+}
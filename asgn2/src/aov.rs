@@ -0,0 +1,233 @@
+use std::str::FromStr;
+
+use cgmath::num_traits::clamp;
+use cgmath::{InnerSpace, Vector3};
+use image::RgbImage;
+
+use material::Material;
+use renderable::Renderable;
+use scene::Scene;
+use stats::Counters;
+use trace::emitters;
+
+/// An arbitrary output variable; a per-pixel image of some property of the
+/// surface seen by each camera ray, used for debugging scenes.
+///
+/// Pixels whose camera rays hit nothing are black in every AOV.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Aov {
+    /// The distance to the surface, where white is the nearest surface in the
+    /// image and black is the farthest.
+    Depth,
+
+    /// The surface normal, with each component mapped from `[-1, 1]` to
+    /// `[0, 1]`.
+    Normal,
+
+    /// A distinct color for each object or mesh in the scene. A run of
+    /// triangles from a scene file is one mesh, so it has one color.
+    ObjectId,
+
+    /// A distinct color for each distinct material in the scene.
+    MaterialId,
+
+    /// The fraction of the scene's lights, and of its emissive objects and
+    /// meshes, that are not in shadow at the surface. Each emissive object or
+    /// mesh counts as one light, partly in shadow if only some of it is
+    /// hidden. White is fully lit, black is fully shadowed.
+    ShadowMask,
+}
+
+impl Aov {
+    /// All the AOVs, in the order they're documented in.
+    pub const ALL: [Aov; 5] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::ShadowMask,
+    ];
+
+    /// Returns the name of the AOV, as accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::ShadowMask => "shadow",
+        }
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Aov, String> {
+        Aov::ALL
+            .iter()
+            .cloned()
+            .find(|aov| aov.name() == s)
+            .ok_or_else(|| format!("Unknown AOV: {}", s))
+    }
+}
+
+impl Scene {
+    /// Renders an arbitrary output variable of the scene to an image.
+    pub fn render_aov(&self, aov: Aov) -> RgbImage {
+        let stats = Counters::default();
+        let material_ids = if aov == Aov::MaterialId {
+            self.material_ids()
+        } else {
+            Vec::new()
+        };
+        let emitters = if aov == Aov::ShadowMask {
+            emitters(self)
+        } else {
+            Vec::new()
+        };
+
+        let mut samples = self.render_pixels(0..self.width, 0..self.height, |x, y| {
+            let ray = self.make_camera_ray(x as f32, y as f32);
+            let (i, obj, dist) = self.closest_collision(ray, &stats)?;
+            let pos = ray.project(dist);
+            Some(match aov {
+                Aov::Depth => {
+                    let depth = (pos - ray.origin).magnitude();
+                    Vector3::new(depth, depth, depth)
+                }
+                Aov::Normal => obj.normal_at(pos).normalize() * 0.5 + Vector3::new(0.5, 0.5, 0.5),
+                Aov::ObjectId => id_color(i),
+                Aov::MaterialId => id_color(material_ids[i]),
+                Aov::ShadowMask => {
+                    let norm = obj.normal_at(pos);
+                    let lights = self
+                        .lights
+                        .iter()
+                        .map(|l| self.light_transmittance(l, pos, norm, &stats));
+                    let emitted = emitters
+                        .iter()
+                        .map(|e| self.emitter_transmittance(e, pos, norm, &stats));
+                    let lit = lights
+                        .chain(emitted)
+                        .map(|t| (t.data[0] + t.data[1] + t.data[2]) / 3.0)
+                        .sum::<f32>();
+                    let count = self.lights.len() + emitters.len();
+                    let lit = if count == 0 { 1.0 } else { lit / count as f32 };
+                    Vector3::new(lit, lit, lit)
+                }
+            })
+        });
+
+        if aov == Aov::Depth {
+            let max = samples
+                .iter()
+                .filter_map(|s| s.map(|v| v.x))
+                .fold(0.0, f32::max);
+            for s in samples.iter_mut().filter_map(|s| s.as_mut()) {
+                let d = if max > 0.0 { 1.0 - s.x / max } else { 1.0 };
+                *s = Vector3::new(d, d, d);
+            }
+        }
+
+        let buf = samples
+            .into_iter()
+            .flat_map(|s| {
+                let s = s.unwrap_or(Vector3::new(0.0, 0.0, 0.0));
+                vec![to_u8(s.x), to_u8(s.y), to_u8(s.z)]
+            })
+            .collect();
+        RgbImage::from_raw(self.width, self.height, buf).unwrap()
+    }
+
+    /// Assigns an ID to each distinct material in the scene, returning the
+    /// ID of each object's material, then each mesh's.
+    fn material_ids(&self) -> Vec<usize> {
+        let mut materials: Vec<Material> = Vec::new();
+        let meshes = self.meshes.iter().map(|m| self.materials[m.material]);
+        self.objects
+            .iter()
+            .map(|o| o.material())
+            .chain(meshes)
+            .map(|m| match materials.iter().position(|&m2| m2 == m) {
+                Some(id) => id,
+                None => {
+                    materials.push(m);
+                    materials.len() - 1
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns an arbitrary (but deterministic) bright color for an ID.
+fn id_color(id: usize) -> Vector3<f32> {
+    let h = (id as u32).wrapping_add(1).wrapping_mul(0x9E37_79B9);
+    let channel = |shift: u32| 0.25 + 0.75 * ((h >> shift) & 0xff) as f32 / 255.0;
+    Vector3::new(channel(24), channel(16), channel(8))
+}
+
+fn to_u8(x: f32) -> u8 {
+    (clamp(x, 0.0, 1.0) * 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use aov::{id_color, to_u8, Aov};
+    use scene::Scene;
+
+    #[test]
+    fn aovs() {
+        // Only the bottom-right pixel's camera ray, which goes through its
+        // corner, hits the sphere; the rest hit the plane behind it. The
+        // sphere shadows the plane where the top-right pixel's ray hits it.
+        let scene: Scene = "film_resolution 2 2\ncamera 0 0 0 0 0 1 0 1 0 45\n\
+                            plane 0 0 10 0 0 -1\nmaterial 0 0 0 1 0 0 0 0 0 1 0 0 0 1\n\
+                            sphere 0 0 5 1\ndirectional_light 1 1 1 0 2 1\n"
+            .parse()
+            .unwrap();
+        let aov = |aov, x, y| scene.render_aov(aov).get_pixel(x, y).data;
+        let id = |id| {
+            let c = id_color(id);
+            [to_u8(c.x), to_u8(c.y), to_u8(c.z)]
+        };
+
+        // The plane is farthest away in the top-left corner.
+        assert_eq!(aov(Aov::Depth, 0, 0), [0, 0, 0]);
+        assert!(aov(Aov::Depth, 1, 1)[0] > aov(Aov::Depth, 1, 0)[0]);
+        assert!(aov(Aov::Depth, 1, 0)[0] > 0);
+
+        // Both surfaces face the camera.
+        assert_eq!(aov(Aov::Normal, 0, 0), [127, 127, 0]);
+        assert_eq!(aov(Aov::Normal, 1, 1), [127, 127, 0]);
+
+        assert_eq!(aov(Aov::ObjectId, 0, 1), id(0));
+        assert_eq!(aov(Aov::ObjectId, 1, 1), id(1));
+        assert_eq!(aov(Aov::MaterialId, 0, 1), id(0));
+        assert_eq!(aov(Aov::MaterialId, 1, 1), id(1));
+
+        assert_eq!(aov(Aov::ShadowMask, 0, 0), [255, 255, 255]);
+        assert_eq!(aov(Aov::ShadowMask, 1, 0), [0, 0, 0]);
+        assert_eq!(aov(Aov::ShadowMask, 1, 1), [255, 255, 255]);
+    }
+
+    #[test]
+    fn emitter_shadows() {
+        // A plane lit only by an emissive sphere off to the side, which a
+        // larger sphere can hide from where the pixel's ray, which goes
+        // through its corner, hits the plane.
+        let scene = |input: &str| -> Scene {
+            format!(
+                "film_resolution 1 1\ncamera 0 0 0 0 0 1 0 1 0 45\nplane 0 0 10 0 0 -1\n\
+                 material 1 1 1 0 0 0 0 0 0 1 0 0 0 1\nemissive 1 1 1\nsphere 20 0 5 1\n{}",
+                input
+            )
+            .parse()
+            .unwrap()
+        };
+        let shadow = |scene: Scene| scene.render_aov(Aov::ShadowMask).get_pixel(0, 0).data;
+
+        assert_eq!(shadow(scene("")), [255, 255, 255]);
+        let hidden = "material 1 1 1 0 0 0 0 0 0 1 0 0 0 1\nsphere 5 5 7.5 2\n";
+        assert_eq!(shadow(scene(hidden)), [0, 0, 0]);
+    }
+}
@@ -1,21 +1,57 @@
 use std::time::Instant;
 
 use cgmath::{Deg, Matrix3, Vector3};
+use image::Rgb;
 use sdl2;
 use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
 use sdl2::render::TextureAccess;
 
-use raytracer::Scene;
+use raytracer::animation::CameraKey;
+use raytracer::light::DynamicLight;
+use raytracer::renderable::{DynamicRenderable, Renderable};
+use raytracer::{Animation, Material, Scene};
 
 const PAN_SPEED: f32 = 0.25;
 const PITCH_SPEED: Deg<f32> = Deg(10.0);
 const ROLL_SPEED: Deg<f32> = Deg(10.0);
 const YAW_SPEED: Deg<f32> = Deg(10.0);
 
-pub fn run(mut scene: Scene) {
+const COLOR_STEP: f32 = 0.05;
+const IOR_STEP: f32 = 0.05;
+const PHONG_FACTOR: f32 = 1.25;
+const INTENSITY_FACTOR: f32 = 1.25;
+
+/// A parameter of the picked object's material that can be changed.
+#[derive(Clone, Copy, Debug)]
+enum Param {
+    Ambient,
+    Diffuse,
+    Specular,
+    Phong,
+    Transmissive,
+    Ior,
+    Emissive,
+}
+
+/// What's being edited: the picked object, which of its material's
+/// parameters, and which light.
+struct Editor {
+    /// The index of the picked object, or mesh, numbered after the objects.
+    picked: Option<usize>,
+    param: Param,
+    light: usize,
+}
+
+/// Runs the GUI. As well as moving the camera, clicking an object picks it,
+/// the number keys 1 to 7 choose a parameter of its material, and the up and
+/// down arrows change it. Clicking a triangle picks the whole mesh it's part
+/// of, which for triangles from a scene file is the run of them it's in. Tab chooses a light, and page up and page down
+/// change its intensity. F2 saves the scene to `save`, if it's given.
+pub fn run(mut scene: Scene, record: Option<&str>, save: Option<&str>) {
     let sdl = sdl2::init().expect("failed to init SDL");
     let video = sdl.video().expect("failed to init SDL video");
 
@@ -31,7 +67,13 @@ pub fn run(mut scene: Scene) {
         .expect("failed to get renderer");
     let texture_builder = canvas.texture_creator();
 
-    loop {
+    let mut path = Animation::default();
+    let mut editor = Editor {
+        picked: None,
+        param: Param::Diffuse,
+        light: 0,
+    };
+    'main: loop {
         let render_start = Instant::now();
 
         for event in events.poll_iter() {
@@ -87,11 +129,44 @@ pub fn run(mut scene: Scene) {
                         scene.camera_direction = Vector3::new(0.0, 0.0, 1.0);
                         scene.camera_up = Vector3::new(0.0, 1.0, 0.0);
                     }
-                    Some(Keycode::Escape) | Some(Keycode::X) => return,
+                    Some(Keycode::Num1) => editor.param = Param::Ambient,
+                    Some(Keycode::Num2) => editor.param = Param::Diffuse,
+                    Some(Keycode::Num3) => editor.param = Param::Specular,
+                    Some(Keycode::Num4) => editor.param = Param::Phong,
+                    Some(Keycode::Num5) => editor.param = Param::Transmissive,
+                    Some(Keycode::Num6) => editor.param = Param::Ior,
+                    Some(Keycode::Num7) => editor.param = Param::Emissive,
+                    Some(Keycode::Up) => editor.adjust(&mut scene, true),
+                    Some(Keycode::Down) => editor.adjust(&mut scene, false),
+                    Some(Keycode::Tab) if !scene.lights.is_empty() => {
+                        editor.light = (editor.light + 1) % scene.lights.len();
+                    }
+                    Some(Keycode::PageUp) => editor.adjust_light(&mut scene, true),
+                    Some(Keycode::PageDown) => editor.adjust_light(&mut scene, false),
+                    Some(Keycode::F2) => match save {
+                        Some(save) => match scene.to_file(save) {
+                            Ok(()) => eprintln!("Saved the scene to {}", save),
+                            Err(err) => eprintln!("Couldn't save the scene to {}: {}", save, err),
+                        },
+                        None => eprintln!("Pass --save to choose a file to save the scene to"),
+                    },
+                    Some(Keycode::Escape) | Some(Keycode::X) => break 'main,
                     _ => {}
                 },
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } => {
+                    editor.picked = match mouse_btn {
+                        MouseButton::Left => scene.object_at(x as u32, y as u32),
+                        _ => None,
+                    };
+                    if let Some(i) = editor.picked {
+                        eprintln!("Picked {}, made of", describe(&scene, i));
+                        eprintln!("{:#?}", material(&scene, i));
+                    }
+                }
                 Event::Window { win_event, .. } => match win_event {
-                    WindowEvent::Close => return,
+                    WindowEvent::Close => break 'main,
                     WindowEvent::Resized(w, h) => {
                         scene.width = w as u32;
                         scene.height = h as u32;
@@ -102,7 +177,19 @@ pub fn run(mut scene: Scene) {
             }
         }
 
-        let buf = scene.render().into_raw();
+        canvas
+            .window_mut()
+            .set_title(&editor.title(&scene))
+            .expect("failed to set title");
+
+        if record.is_some() {
+            let frame = path.camera.keyframes.len() as f32;
+            path.camera
+                .keyframes
+                .push(CameraKey::from_scene(frame, &scene));
+        }
+
+        let buf = scene.render().to_rgb8().into_raw();
         assert_eq!(buf.len() as u32, scene.width * scene.height * 3);
         let rect = Rect::new(0, 0, scene.width, scene.height);
         let mut texture = texture_builder
@@ -111,7 +198,8 @@ pub fn run(mut scene: Scene) {
                 TextureAccess::Streaming,
                 scene.width,
                 scene.height,
-            ).expect("failed to create image");
+            )
+            .expect("failed to create image");
 
         texture
             .update(Some(rect), &buf, scene.width as usize * 3)
@@ -129,4 +217,114 @@ pub fn run(mut scene: Scene) {
             render_time_secs * 1000 + (render_time_ns / 1_000_000) as u64
         );
     }
+
+    if let Some(record) = record {
+        path.to_file(record).expect("Failed to write camera path");
+    }
+}
+
+impl Editor {
+    /// Raises or lowers the parameter being edited of the picked object's
+    /// material.
+    fn adjust(&self, scene: &mut Scene, up: bool) {
+        let material = match self.picked.and_then(|i| scene.material_mut(i)) {
+            Some(material) => material,
+            None => return,
+        };
+        let step = |x: f32, step: f32| if up { x + step } else { x - step };
+        let color = |c: &mut Rgb<f32>| {
+            for x in &mut c.data {
+                *x = step(*x, COLOR_STEP).clamp(0.0, 1.0);
+            }
+        };
+        match self.param {
+            Param::Ambient => color(&mut material.ambient),
+            Param::Diffuse => color(&mut material.diffuse),
+            Param::Specular => color(&mut material.specular),
+            Param::Phong if up => material.phong *= PHONG_FACTOR,
+            Param::Phong => material.phong /= PHONG_FACTOR,
+            Param::Transmissive => color(&mut material.transmissive),
+            Param::Ior => material.ior = step(material.ior, IOR_STEP).max(1.0),
+            Param::Emissive => color(&mut material.emissive),
+        }
+    }
+
+    /// Raises or lowers the intensity of the selected light.
+    fn adjust_light(&self, scene: &mut Scene, up: bool) {
+        let intensity = match scene.lights.get_mut(self.light) {
+            Some(DynamicLight::Directional(l)) => &mut l.intensity,
+            Some(DynamicLight::Point(l)) => &mut l.intensity,
+            Some(DynamicLight::Spot(l)) => &mut l.intensity,
+            None => return,
+        };
+        if up {
+            *intensity *= INTENSITY_FACTOR;
+        } else {
+            *intensity /= INTENSITY_FACTOR;
+        }
+    }
+
+    /// Returns the window title, describing what's being edited.
+    fn title(&self, scene: &Scene) -> String {
+        let mut title = crate_name!().to_string();
+        if let Some(i) = self.picked {
+            let material = material(scene, i);
+            let value = match self.param {
+                Param::Ambient => color(material.ambient),
+                Param::Diffuse => color(material.diffuse),
+                Param::Specular => color(material.specular),
+                Param::Phong => material.phong.to_string(),
+                Param::Transmissive => color(material.transmissive),
+                Param::Ior => material.ior.to_string(),
+                Param::Emissive => color(material.emissive),
+            };
+            title += &format!(" - {}: {:?} {}", describe(scene, i), self.param, value);
+        }
+        if let Some(light) = scene.lights.get(self.light) {
+            let (kind, intensity) = match *light {
+                DynamicLight::Directional(ref l) => ("directional", l.intensity),
+                DynamicLight::Point(ref l) => ("point", l.intensity),
+                DynamicLight::Spot(ref l) => ("spot", l.intensity),
+            };
+            title += &format!(" - {} light {}: intensity {}", kind, self.light, intensity);
+        }
+        title
+    }
+}
+
+/// Describes an object or mesh by its type and position.
+fn describe(scene: &Scene, i: usize) -> String {
+    let (kind, position) = match scene.objects.get(i) {
+        Some(DynamicRenderable::Plane(p)) => ("plane", p.point),
+        Some(DynamicRenderable::Sphere(s)) => ("sphere", s.position),
+        Some(DynamicRenderable::Triangle(t)) => {
+            let (v1, v2, v3) = t.vertices;
+            ("triangle", (v1 + v2 + v3) / 3.0)
+        }
+        None => {
+            let mesh = &scene.meshes[i - scene.objects.len()];
+            let sum = mesh
+                .positions
+                .iter()
+                .fold(Vector3::new(0.0, 0.0, 0.0), |a, &p| a + p);
+            ("mesh", sum / mesh.positions.len().max(1) as f32)
+        }
+    };
+    format!(
+        "{} {} at ({}, {}, {})",
+        kind, i, position.x, position.y, position.z
+    )
+}
+
+/// Returns the material of an object or mesh.
+fn material(scene: &Scene, i: usize) -> Material {
+    match scene.objects.get(i) {
+        Some(obj) => obj.material(),
+        None => scene.materials[scene.meshes[i - scene.objects.len()].material],
+    }
+}
+
+/// Formats a color as its three channels.
+fn color(c: Rgb<f32>) -> String {
+    format!("{} {} {}", c.data[0], c.data[1], c.data[2])
 }
@@ -11,64 +11,389 @@ extern crate sdl2;
 #[cfg(feature = "sdl2")]
 mod gui;
 
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
 use std::path::Path;
+use std::process::exit;
+#[cfg(unix)]
+use std::str::FromStr;
+use std::time::Instant;
 
-use raytracer::Scene;
+use clap::ArgMatches;
+use log::{LogLevel, LogLevelFilter, LogMetadata, LogRecord};
+use raytracer::animation::{frame_path, CameraKey};
+use raytracer::distributed;
+use raytracer::framebuffer::{Framebuffer, PixelFormat};
+use raytracer::parser::Format;
+#[cfg(unix)]
+use raytracer::server::{request, Limits, RenderRequest, Server};
+use raytracer::util::millis;
+use raytracer::{Animation, Aov, Scene, Severity};
 
 fn main() {
+    log::set_logger(|max| {
+        max.set(LogLevelFilter::Warn);
+        Box::new(StderrLogger)
+    })
+    .expect("Failed to set the logger");
+
     let matches = clap_app!(raytracer =>
         (version: crate_version!())
         (author: crate_authors!())
         (about: crate_description!())
-        (@arg INPUT: +required "The input file to read.")
+        (@setting SubcommandsNegateReqs)
+        (@arg INPUT: +required "The scene file to read. JSON, TOML, and YAML files are read as structured scenes.")
         (@arg OUTPUT: -o +takes_value "The output file to write to. Overrides the scene's default.")
         (@arg HEIGHT: -h +takes_value "Overrides the height of the output image.")
         (@arg WIDTH: -w +takes_value "Overrides the width of the output image.")
+        (@arg ANIMATION: --animation +takes_value
+            "A JSON, TOML, or YAML file of keyframes to animate the scene with. Renders every frame up to the last keyframe, unless --frames is given.")
+        (@arg FRAMES: --frames +takes_value
+            "Renders the frames in the range START..END of the animation, adding the frame number to the output file name. A run of #s in the name is replaced with the zero-padded frame number.")
+        (@arg RECORD: --record +takes_value
+            "Records the camera's path through the GUI to a JSON, TOML, or YAML file, which can be rendered with --animation.")
+        (@arg SAVE: --save +takes_value
+            "The scene file the GUI saves edits to when F2 is pressed. Without it, F2 does nothing. Saving expands meshes, includes, and defines, so this shouldn't be the input file.")
+        (@arg AOV: --aov +takes_value +multiple number_of_values(1)
+            "Writes an arbitrary output variable to a file, as NAME=FILE. NAME is one of depth, normal, object_id, material_id, or shadow.")
+        (@arg SAMPLES: --samples +takes_value "Overrides the number of samples per pixel.")
+        (@arg WORKERS: --workers +takes_value +multiple use_delimiter(true)
+            "Renders the image on the workers at the given comma-separated addresses, started with the worker subcommand.")
+        (@arg TILE_SIZE: --("tile-size") +takes_value
+            "The width and height of the tiles sent to workers, in pixels.")
+        (@arg STATS: --stats "Prints render statistics to stderr.")
+        (@arg STRICT: --strict "Refuses to render scenes with warnings, as well as errors.")
+        (@arg TRANSPARENT: --transparent
+            "Makes pixels that hit nothing transparent instead of the background color. Requires a PNG output file.")
+        (@subcommand serve =>
+            (about: "Serves render requests over a Unix socket, keeping parsed scenes in memory.")
+            (@arg SOCKET: +required "The path of the socket to listen on.")
+            (@arg SCENES: +multiple "Scene files to parse before serving requests.")
+            (@arg ROOT: --root +takes_value
+                "The directory requested scenes must be in. Defaults to the current directory.")
+            (@arg MAX_PIXELS: --("max-pixels") +takes_value "The most pixels a requested image can have.")
+            (@arg MAX_SAMPLES: --("max-samples") +takes_value
+                "The most samples per pixel a request can ask for.")
+            (@arg THREADS: --threads +takes_value "The number of requests handled at once.")
+        )
+        (@subcommand worker =>
+            (about: "Renders tiles of images for other raytracer processes given --workers.")
+            (@arg ADDRESS: +required
+                "The address to listen on, e.g. 127.0.0.1:7000. Workers render any scene sent to them, so this should only be reachable from trusted machines.")
+        )
+        (@subcommand client =>
+            (about: "Asks a render server to render a scene.")
+            (@arg SOCKET: +required "The path of the server's socket.")
+            (@arg SCENE: +required "The scene file to render, as seen by the server.")
+            (@arg OUTPUT: -o +takes_value +required "The PNG file to write to.")
+            (@arg HEIGHT: -h +takes_value "Overrides the height of the output image.")
+            (@arg WIDTH: -w +takes_value "Overrides the width of the output image.")
+            (@arg SAMPLES: --samples +takes_value "Overrides the number of samples per pixel.")
+            (@arg CAMERA: --camera +takes_value
+                "Overrides the camera, as the ten arguments to the scene file's camera directive.")
+        )
     ).get_matches();
+    match matches.subcommand() {
+        ("serve", Some(matches)) => return serve(matches),
+        ("client", Some(matches)) => return client(matches),
+        ("worker", Some(matches)) => return worker(matches),
+        _ => {}
+    }
+    let print_stats = matches.is_present("STATS");
 
     let input_file = matches.value_of("INPUT").unwrap();
-    let input = read_file(input_file);
-
-    let mut scene: Scene = input.parse().expect("Failed to parse scene");
+    let parse_start = Instant::now();
+    let mut scene = match Scene::from_file(input_file) {
+        Ok(scene) => scene,
+        Err(errs) => {
+            eprintln!("{}", errs);
+            exit(1);
+        }
+    };
+    if print_stats {
+        eprintln!("parse time:         {}ms", millis(parse_start.elapsed()));
+    }
     if let Some(h) = matches.value_of("HEIGHT") {
         scene.height = h.parse().expect("Couldn't parse -h argument");
     }
     if let Some(w) = matches.value_of("WIDTH") {
         scene.width = w.parse().expect("Couldn't parse -w argument");
     }
+    if let Some(n) = matches.value_of("SAMPLES") {
+        scene.samples = n.parse().expect("Couldn't parse --samples argument");
+    }
+    if matches.is_present("TRANSPARENT") {
+        scene.transparent_background = true;
+    }
+
+    let issues = scene.validate();
+    for issue in &issues {
+        eprintln!("{}", issue);
+    }
+    let fail_on = if matches.is_present("STRICT") {
+        Severity::Warning
+    } else {
+        Severity::Error
+    };
+    if issues.iter().any(|i| i.severity() >= fail_on) {
+        exit(1);
+    }
+
+    for arg in matches.values_of("AOV").into_iter().flatten() {
+        let mut parts = arg.splitn(2, '=');
+        let aov: Aov = parts
+            .next()
+            .unwrap()
+            .parse()
+            .expect("Couldn't parse --aov argument");
+        let path = parts
+            .next()
+            .expect("--aov argument must be of the form NAME=FILE");
+        let aov_start = Instant::now();
+        scene
+            .render_aov(aov)
+            .save(path)
+            .expect("Failed to write AOV image");
+        if print_stats {
+            eprintln!("{} AOV time: {}ms", aov.name(), millis(aov_start.elapsed()));
+        }
+    }
 
     let path = match matches.value_of("OUTPUT") {
         Some(p) => Some(p.into()),
         None => scene.output_image.clone(),
     };
-    if let Some(output_path) = path {
-        let image = scene.render();
-        image.save(output_path).expect("Failed to write image")
+    if matches.is_present("FRAMES") || matches.is_present("ANIMATION") {
+        let output_path = path.expect("Rendering an animation requires an output file");
+        let animation = match matches.value_of("ANIMATION") {
+            Some(path) => match Animation::from_file(path) {
+                Ok(animation) => animation,
+                Err(errs) => {
+                    eprintln!("{}", errs);
+                    exit(1);
+                }
+            },
+            None => Animation::default(),
+        };
+        if let Err(err) = animation.check(&scene) {
+            eprintln!("error: {}", err);
+            exit(1);
+        }
+
+        let (start, end) = match matches.value_of("FRAMES") {
+            Some(frames) => {
+                let mut parts = frames.splitn(2, "..");
+                let start: u32 = parts
+                    .next()
+                    .unwrap()
+                    .parse()
+                    .expect("Couldn't parse --frames argument");
+                let end: u32 = parts
+                    .next()
+                    .expect("--frames argument must be of the form START..END")
+                    .parse()
+                    .expect("Couldn't parse --frames argument");
+                (start, end)
+            }
+            None => {
+                let frames = animation.frames();
+                (frames.start, frames.end)
+            }
+        };
+        let render_start = Instant::now();
+        animation.render_frames(&scene, start..end, |frame, image| {
+            save(
+                &image,
+                frame_path(&output_path, frame),
+                scene.transparent_background,
+            );
+        });
+        if print_stats {
+            eprintln!("frames:             {}", end.saturating_sub(start));
+            eprintln!("total time:         {}ms", millis(render_start.elapsed()));
+        }
+    } else if let (Some(output_path), Some(workers)) = (&path, matches.values_of("WORKERS")) {
+        let workers = workers.collect::<Vec<_>>();
+        let tile_size = matches
+            .value_of("TILE_SIZE")
+            .map_or(distributed::TILE_SIZE, |n| {
+                n.parse().expect("Couldn't parse --tile-size argument")
+            });
+        let render_start = Instant::now();
+        let image = match distributed::render(&scene, &workers, tile_size) {
+            Ok(image) => image,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                exit(1);
+            }
+        };
+        if print_stats {
+            eprintln!("render time:        {}ms", millis(render_start.elapsed()));
+        }
+        save(&image, output_path, scene.transparent_background);
+    } else if let Some(output_path) = path {
+        let (image, stats) = scene.render_with_stats();
+        let write_start = Instant::now();
+        save(&image, output_path, scene.transparent_background);
+        if print_stats {
+            eprintln!("{}", stats);
+            eprintln!("write time:         {}ms", millis(write_start.elapsed()));
+        }
     } else {
-        gui::run(scene)
+        let record = matches.value_of("RECORD");
+        if record.is_some_and(|path| Format::from_path(path).is_none()) {
+            eprintln!("error: camera paths must be recorded to JSON, TOML, or YAML files");
+            exit(1);
+        }
+        gui::run(scene, record, matches.value_of("SAVE"))
     }
 }
 
-fn read_file<P: AsRef<Path>>(path: P) -> String {
-    let mut f = File::open(path).expect("Failed to open input file");
-    let mut buf = String::new();
-    f.read_to_string(&mut buf)
-        .expect("Failed to read input file");
-    buf
+/// Runs a render server.
+#[cfg(unix)]
+fn serve(matches: &ArgMatches) {
+    let mut limits = Limits::default();
+    if let Some(root) = matches.value_of("ROOT") {
+        limits.root = root.into();
+    }
+    if let Some(n) = matches.value_of("MAX_PIXELS") {
+        limits.max_pixels = n.parse().expect("Couldn't parse --max-pixels argument");
+    }
+    if let Some(n) = matches.value_of("MAX_SAMPLES") {
+        limits.max_samples = n.parse().expect("Couldn't parse --max-samples argument");
+    }
+    if let Some(n) = matches.value_of("THREADS") {
+        limits.threads = n.parse().expect("Couldn't parse --threads argument");
+    }
+    let server = Server::with_limits(limits);
+    for path in matches.values_of("SCENES").into_iter().flatten() {
+        if let Err(err) = server.load(path) {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    }
+
+    let socket = matches.value_of("SOCKET").unwrap();
+    if fs::metadata(socket).is_ok_and(|m| m.file_type().is_socket()) {
+        fs::remove_file(socket).expect("Failed to remove old socket");
+    }
+    let listener = UnixListener::bind(socket).expect("Failed to listen on socket");
+    server
+        .serve(&listener)
+        .expect("Failed to accept connection");
+}
+
+/// Sends a request to a render server, and saves the image it returns.
+#[cfg(unix)]
+fn client(matches: &ArgMatches) {
+    fn parse<T: FromStr>(matches: &ArgMatches, name: &str, flag: &str) -> Option<T> {
+        let value = matches.value_of(name)?;
+        Some(
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Couldn't parse {} argument", flag)),
+        )
+    }
+
+    let camera = matches.value_of("CAMERA").map(|camera| {
+        let f = camera
+            .split_whitespace()
+            .map(|f| f.parse().expect("Couldn't parse --camera argument"))
+            .collect::<Vec<f32>>();
+        assert_eq!(f.len(), 10, "--camera takes 10 numbers");
+        CameraKey {
+            position: Some([f[0], f[1], f[2]]),
+            direction: Some([f[3], f[4], f[5]]),
+            up: Some([f[6], f[7], f[8]]),
+            half_angle: Some(f[9]),
+            ..CameraKey::default()
+        }
+    });
+    let req = RenderRequest {
+        scene: matches.value_of("SCENE").unwrap().into(),
+        camera,
+        width: parse(matches, "WIDTH", "-w"),
+        height: parse(matches, "HEIGHT", "-h"),
+        samples: parse(matches, "SAMPLES", "--samples"),
+    };
+    let png = match request(matches.value_of("SOCKET").unwrap(), &req) {
+        Ok(png) => png,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
+    fs::write(matches.value_of("OUTPUT").unwrap(), png).expect("Failed to write image");
+}
+
+#[cfg(not(unix))]
+fn serve(_: &ArgMatches) {
+    eprintln!("The render server needs Unix sockets");
+    exit(1);
+}
+
+#[cfg(not(unix))]
+fn client(_: &ArgMatches) {
+    eprintln!("The render server needs Unix sockets");
+    exit(1);
+}
+
+/// Runs a worker for distributed renders.
+fn worker(matches: &ArgMatches) {
+    let address = matches.value_of("ADDRESS").unwrap();
+    let listener = TcpListener::bind(address).expect("Failed to listen on address");
+    distributed::serve_worker(&listener).expect("Failed to accept connection");
+}
+
+/// Prints the warnings and errors the library logs, such as those from
+/// connections a server failed to handle, to stderr.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= LogLevel::Warn
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if self.enabled(record.metadata()) {
+            let level = record.level().to_string().to_lowercase();
+            eprintln!("{}: {}", level, record.args());
+        }
+    }
+}
+
+/// Saves a rendered image, with an alpha channel if the background is
+/// transparent.
+fn save<P: AsRef<Path>>(image: &Framebuffer, path: P, transparent: bool) {
+    if transparent {
+        let format = PixelFormat::from_path_with_alpha(&path)
+            .expect("Transparent backgrounds can only be saved as PNG");
+        image.save_as(path, format)
+    } else {
+        image.save(path)
+    }
+    .expect("Failed to write image")
 }
 
 #[cfg(not(feature = "sdl2"))]
 mod gui {
-    use std::process::{Command, Stdio};
+    use std::process::{exit, Command, Stdio};
 
     use image::png::PNGEncoder;
     use image::ColorType;
     use raytracer::Scene;
 
-    pub fn run(scene: Scene) {
-        let image = scene.render();
+    pub fn run(scene: Scene, record: Option<&str>, _save: Option<&str>) {
+        if record.is_some() {
+            eprintln!("Recording a camera path requires the GUI, which needs the sdl2 feature");
+            exit(1);
+        }
+
+        let image = scene.render().to_rgb8();
         let child = Command::new("display")
             .stdin(Stdio::piped())
             .stdout(Stdio::null())
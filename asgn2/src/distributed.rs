@@ -0,0 +1,385 @@
+//! Rendering scenes across worker processes, one tile at a time.
+//!
+//! Workers listen on a TCP port. The coordinator connects to each worker and
+//! sends it the scene as a line of JSON, then sends tiles to render, each as
+//! a line of JSON. A worker replies to each tile with its pixels in row-major
+//! order, each as four little-endian `f32`s.
+//!
+//! Each worker is sent one tile at a time, so faster workers render more of
+//! the image. If a worker's connection fails, the tile it was rendering goes
+//! back in the queue for the other workers; the render only fails if every
+//! worker does. A worker that doesn't reply within `TIMEOUT` is treated as
+//! failed.
+//!
+//! Workers render whatever scene any peer that connects sends them, so they
+//! should only listen on interfaces that untrusted machines can't reach.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+
+use framebuffer::Framebuffer;
+use scene::Scene;
+use stats::Counters;
+
+/// The default width and height of tiles, in pixels.
+pub const TILE_SIZE: u32 = 64;
+
+/// How long the coordinator waits for a worker to accept a message or reply
+/// to a tile, so that a worker whose machine hangs or loses the network
+/// doesn't hold up the render forever.
+pub const TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The most pixels a worker will render in one tile.
+const MAX_TILE_PIXELS: usize = 1 << 22;
+
+/// A rectangle of the image.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Tile {
+    /// The column of the tile's left edge.
+    pub x: u32,
+
+    /// The row of the tile's top edge.
+    pub y: u32,
+
+    /// The width of the tile.
+    pub width: u32,
+
+    /// The height of the tile.
+    pub height: u32,
+}
+
+impl Tile {
+    /// Splits an image into tiles of at most the given size, in row-major
+    /// order.
+    pub fn split(width: u32, height: u32, size: u32) -> Vec<Tile> {
+        let size = size.max(1);
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(size as usize) {
+            for x in (0..width).step_by(size as usize) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// Returns the number of pixels in the tile.
+    fn len(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+}
+
+/// The first message to a worker, giving it the scene to render.
+#[derive(Serialize, Deserialize)]
+struct Job {
+    scene: Scene,
+
+    /// The scene's camera half-angle is serialized in degrees, which doesn't
+    /// round-trip exactly, so its tangent is sent as well.
+    camera_half_angle_tan: f32,
+}
+
+impl Scene {
+    /// Renders a tile of the scene, returning its pixels in row-major order.
+    pub fn render_tile(&self, tile: Tile) -> Vec<[f32; 4]> {
+        let stats = Counters::default();
+        self.render_region(
+            tile.x..tile.x + tile.width,
+            tile.y..tile.y + tile.height,
+            &stats,
+        )
+    }
+}
+
+/// Renders a scene on the workers at the given addresses, splitting it into
+/// tiles of at most `tile_size` pixels square.
+pub fn render<A>(scene: &Scene, workers: &[A], tile_size: u32) -> IoResult<Framebuffer>
+where
+    A: ToSocketAddrs + Sync,
+{
+    let tiles = Tile::split(scene.width, scene.height, tile_size);
+    let job = serde_json::to_vec(&Job {
+        scene: scene.clone(),
+        camera_half_angle_tan: scene.camera_half_angle_tan,
+    })?;
+    let queue = Queue {
+        state: Mutex::new(QueueState {
+            remaining: tiles.len(),
+            tiles: tiles.into(),
+            workers: workers.len(),
+            error: None,
+        }),
+        changed: Condvar::new(),
+    };
+    let pixels = Mutex::new(vec![[0.0; 4]; scene.width as usize * scene.height as usize]);
+
+    thread::scope(|s| {
+        for addr in workers {
+            let (queue, pixels, job) = (&queue, &pixels, &job);
+            s.spawn(move || {
+                let result = TcpStream::connect(addr)
+                    .and_then(|stream| run_worker(stream, job, queue, scene.width, pixels));
+                queue.worker_finished(result.err());
+            });
+        }
+    });
+
+    let state = queue
+        .state
+        .into_inner()
+        .unwrap_or_else(|err| err.into_inner());
+    if state.remaining > 0 {
+        let err = state
+            .error
+            .unwrap_or_else(|| IoError::new(ErrorKind::NotConnected, "no workers were given"));
+        return Err(IoError::new(
+            err.kind(),
+            format!("every worker failed; the last error was: {}", err),
+        ));
+    }
+    let pixels = pixels.into_inner().unwrap_or_else(|err| err.into_inner());
+    Ok(Framebuffer::new(scene.width, scene.height, pixels))
+}
+
+/// Sends tiles to a worker until there are none left, copying the pixels it
+/// returns into the image.
+fn run_worker(
+    mut stream: TcpStream,
+    job: &[u8],
+    queue: &Queue,
+    width: u32,
+    pixels: &Mutex<Vec<[f32; 4]>>,
+) -> IoResult<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.write_all(job)?;
+    stream.write_all(b"\n")?;
+    let mut buf = Vec::new();
+    while let Some(tile) = queue.take() {
+        let result = render_remote(&mut stream, tile, &mut buf);
+        if result.is_err() {
+            queue.put_back(tile);
+            return result;
+        }
+
+        let mut pixels = pixels.lock().unwrap_or_else(|err| err.into_inner());
+        for (row, chunk) in buf.chunks(16 * tile.width as usize).enumerate() {
+            let start = (tile.y as usize + row) * width as usize + tile.x as usize;
+            for (px, bytes) in pixels[start..].iter_mut().zip(chunk.chunks(16)) {
+                for (c, b) in px.iter_mut().zip(bytes.chunks(4)) {
+                    *c = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                }
+            }
+        }
+        drop(pixels);
+        queue.tile_done();
+    }
+    Ok(())
+}
+
+/// Asks a worker to render a tile, reading its pixels into `buf`.
+fn render_remote(stream: &mut TcpStream, tile: Tile, buf: &mut Vec<u8>) -> IoResult<()> {
+    let mut request = serde_json::to_vec(&tile)?;
+    request.push(b'\n');
+    stream.write_all(&request)?;
+    buf.resize(tile.len() * 16, 0);
+    stream.read_exact(buf)
+}
+
+/// The tiles waiting to be rendered, shared between the threads talking to
+/// each worker.
+struct Queue {
+    state: Mutex<QueueState>,
+
+    /// Signalled when a tile is put back, finished, or a worker fails.
+    changed: Condvar,
+}
+
+struct QueueState {
+    /// The tiles no worker is rendering.
+    tiles: VecDeque<Tile>,
+
+    /// The number of tiles that haven't been rendered yet, including those
+    /// being rendered.
+    remaining: usize,
+
+    /// The number of workers that haven't stopped.
+    workers: usize,
+
+    /// The most recent error from a worker.
+    error: Option<IoError>,
+}
+
+impl Queue {
+    /// Takes a tile to render, waiting for one to be put back if the rest
+    /// are being rendered. Returns `None` once every tile is done.
+    fn take(&self) -> Option<Tile> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        loop {
+            if let Some(tile) = state.tiles.pop_front() {
+                return Some(tile);
+            } else if state.remaining == 0 {
+                return None;
+            }
+            state = self
+                .changed
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Returns a tile that couldn't be rendered to the queue.
+    fn put_back(&self, tile: Tile) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.tiles.push_back(tile);
+        self.changed.notify_all();
+    }
+
+    /// Records that a tile has been rendered.
+    fn tile_done(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.remaining -= 1;
+        self.changed.notify_all();
+    }
+
+    /// Records that a worker has stopped, with the error it failed with, if
+    /// any. Once every worker has stopped, the rest of the tiles can't be
+    /// rendered, so the queue is emptied to wake anyone waiting on it.
+    fn worker_finished(&self, error: Option<IoError>) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.workers -= 1;
+        if error.is_some() {
+            state.error = error;
+        }
+        if state.workers == 0 {
+            state.tiles.clear();
+        }
+        self.changed.notify_all();
+    }
+}
+
+/// Serves the coordinators that connect to a listener, rendering the tiles
+/// they ask for. Each connection is handled on its own thread.
+///
+/// Any peer that can connect can have the worker render any scene, so the
+/// listener should only be bound to interfaces trusted machines use.
+pub fn serve_worker(listener: &TcpListener) -> IoResult<()> {
+    thread::scope(|s| {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            s.spawn(move || {
+                if let Err(err) = handle_coordinator(stream) {
+                    error!("{}", err);
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+/// Reads a scene from a coordinator, then renders tiles of it until the
+/// coordinator disconnects.
+fn handle_coordinator(stream: TcpStream) -> IoResult<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let job: Job = serde_json::from_str(&line)?;
+    let mut scene = job.scene;
+    scene.camera_half_angle_tan = job.camera_half_angle_tan;
+
+    let mut buf = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let tile: Tile = serde_json::from_str(&line)?;
+        let inside =
+            |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|end| end <= max);
+        if !inside(tile.x, tile.width, scene.width) || !inside(tile.y, tile.height, scene.height) {
+            let msg = format!("{:?} is outside the image", tile);
+            return Err(IoError::new(ErrorKind::InvalidInput, msg));
+        }
+        if tile.len() > MAX_TILE_PIXELS {
+            let msg = format!("{:?} has more than {} pixels", tile, MAX_TILE_PIXELS);
+            return Err(IoError::new(ErrorKind::InvalidInput, msg));
+        }
+
+        buf.clear();
+        for px in scene.render_tile(tile) {
+            for c in &px {
+                buf.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        writer.write_all(&buf)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use distributed::{render, serve_worker, Tile};
+    use scene::Scene;
+
+    #[test]
+    fn split() {
+        let tiles = Tile::split(5, 3, 2);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[5],
+            Tile {
+                x: 4,
+                y: 2,
+                width: 1,
+                height: 1,
+            }
+        );
+        assert_eq!(tiles.iter().map(|t| t.width * t.height).sum::<u32>(), 15);
+    }
+
+    #[test]
+    fn render_on_workers() {
+        let mut scene: Scene = include_str!("../scenes/sphere.scn").parse().unwrap();
+        scene.width = 37;
+        scene.height = 23;
+        scene.camera_half_angle_tan = 0.7;
+
+        let mut workers = Vec::new();
+        for _ in 0..2 {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            workers.push(listener.local_addr().unwrap());
+            thread::spawn(move || serve_worker(&listener));
+        }
+
+        // A worker that dies after taking its first tile.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        workers.push(listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            reader.read_line(&mut line).unwrap();
+        });
+
+        let image = render(&scene, &workers, 8).unwrap();
+        assert_eq!(image.pixels(), scene.render().pixels());
+
+        let dead = vec![workers[2]];
+        assert!(render(&scene, &dead, 8).is_err());
+    }
+}
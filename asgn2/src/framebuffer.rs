@@ -0,0 +1,188 @@
+//! The image a scene is rendered to.
+
+use std::fs::File;
+use std::io::{BufWriter, Error as IoError, ErrorKind, Result as IoResult};
+use std::path::Path;
+
+use cgmath::num_traits::clamp;
+use image::hdr::HDREncoder;
+use image::{self, ColorType, ImageBuffer, Rgb, RgbImage, RgbaImage};
+
+/// The pixel formats a `Framebuffer` can be read back as.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PixelFormat {
+    /// 8-bit RGB.
+    Rgb8,
+
+    /// 8-bit RGBA. Pixels whose camera rays hit nothing have an alpha of 0.
+    ///
+    /// For the image to be composited correctly, the scene should have been
+    /// rendered with `Scene::transparent_background` set.
+    Rgba8,
+
+    /// 16-bit RGB.
+    Rgb16,
+
+    /// 32-bit floating-point RGB.
+    Rgb32F,
+}
+
+impl PixelFormat {
+    /// Picks the pixel format with an alpha channel for the image format
+    /// implied by the extension of the given path, or `None` if the image
+    /// format doesn't support alpha.
+    pub fn from_path_with_alpha<P: AsRef<Path>>(path: P) -> Option<PixelFormat> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match &*ext {
+            "png" => Some(PixelFormat::Rgba8),
+            _ => None,
+        }
+    }
+
+    /// Picks the pixel format best suited for the image format implied by the
+    /// extension of the given path, or `None` if the extension isn't a known
+    /// image format.
+    ///
+    /// PPM files are written as 16-bit and Radiance HDR files as floating
+    /// point. Other formats are written as 8-bit RGB. These are all the
+    /// formats the `image` crate can write; it can read others, such as TGA,
+    /// but not write them.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<PixelFormat> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match &*ext {
+            "bmp" | "ico" | "jpeg" | "jpg" | "png" => Some(PixelFormat::Rgb8),
+            "ppm" => Some(PixelFormat::Rgb16),
+            "hdr" => Some(PixelFormat::Rgb32F),
+            _ => None,
+        }
+    }
+}
+
+/// A rendered image, holding an RGBA color for each pixel.
+///
+/// Colors are stored as floats in `[0, 1]`. The alpha channel is the coverage
+/// of the pixel; it is 1 where the camera ray hit an opaque object, 0 where it
+/// hit nothing, and in between where it passed through transmissive objects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Framebuffer {
+    /// Creates a framebuffer from pixels in row-major order.
+    ///
+    /// Panics if the number of pixels is not `width * height`.
+    pub fn new(width: u32, height: u32, pixels: Vec<[f32; 4]>) -> Framebuffer {
+        assert_eq!(pixels.len(), width as usize * height as usize);
+        Framebuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Returns the width of the image.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the image.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the pixels of the image, in row-major order.
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+
+    /// Reads the image back as 8-bit RGB.
+    pub fn to_rgb8(&self) -> RgbImage {
+        let buf = self.channels(3, f32_to_u8);
+        RgbImage::from_raw(self.width, self.height, buf).unwrap()
+    }
+
+    /// Reads the image back as 8-bit RGBA.
+    pub fn to_rgba8(&self) -> RgbaImage {
+        let buf = self.channels(4, f32_to_u8);
+        RgbaImage::from_raw(self.width, self.height, buf).unwrap()
+    }
+
+    /// Reads the image back as 16-bit RGB.
+    pub fn to_rgb16(&self) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        let buf = self.channels(3, f32_to_u16);
+        ImageBuffer::from_raw(self.width, self.height, buf).unwrap()
+    }
+
+    /// Reads the image back as floating-point RGB.
+    pub fn to_rgb32f(&self) -> ImageBuffer<Rgb<f32>, Vec<f32>> {
+        let buf = self.channels(3, |x| x);
+        ImageBuffer::from_raw(self.width, self.height, buf).unwrap()
+    }
+
+    /// Saves the image, picking the pixel format from the path's extension
+    /// with `PixelFormat::from_path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> IoResult<()> {
+        let path = path.as_ref();
+        let format = PixelFormat::from_path(path).ok_or_else(|| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown image format for {}", path.display()),
+            )
+        })?;
+        self.save_as(path, format)
+    }
+
+    /// Saves the image in the given pixel format. The image format is
+    /// determined by the path's extension, except that `Rgb32F` images are
+    /// always written as Radiance HDR.
+    pub fn save_as<P: AsRef<Path>>(&self, path: P, format: PixelFormat) -> IoResult<()> {
+        let (w, h) = (self.width, self.height);
+        match format {
+            PixelFormat::Rgb8 => image::save_buffer(path, &self.to_rgb8(), w, h, ColorType::RGB(8)),
+            PixelFormat::Rgba8 => {
+                image::save_buffer(path, &self.to_rgba8(), w, h, ColorType::RGBA(8))
+            }
+            PixelFormat::Rgb16 => {
+                // Both PNG and PPM store 16-bit samples big-endian.
+                let mut buf = Vec::with_capacity(self.pixels.len() * 6);
+                for x in self.to_rgb16().into_raw() {
+                    buf.extend_from_slice(&x.to_be_bytes());
+                }
+                image::save_buffer(path, &buf, w, h, ColorType::RGB(16))
+            }
+            PixelFormat::Rgb32F => {
+                let pixels = self
+                    .pixels
+                    .iter()
+                    .map(|p| Rgb {
+                        data: [p[0], p[1], p[2]],
+                    })
+                    .collect::<Vec<_>>();
+                let f = BufWriter::new(File::create(path)?);
+                HDREncoder::new(f).encode(&pixels, w as usize, h as usize)
+            }
+        }
+    }
+
+    /// Converts the first `n` channels of each pixel with the given function.
+    fn channels<T, F: Fn(f32) -> T>(&self, n: usize, f: F) -> Vec<T> {
+        let mut buf = Vec::with_capacity(self.pixels.len() * n);
+        for p in &self.pixels {
+            buf.extend(p[..n].iter().map(|&x| f(x)));
+        }
+        buf
+    }
+}
+
+fn f32_to_u8(x: f32) -> u8 {
+    debug_assert!(x <= 1.0);
+    debug_assert!(x >= 0.0);
+    (x * 255.0) as u8
+}
+
+fn f32_to_u16(x: f32) -> u16 {
+    (clamp(x, 0.0, 1.0) * 65535.0) as u16
+}
@@ -7,20 +7,41 @@ extern crate float_ord;
 extern crate image;
 #[macro_use]
 extern crate log;
-#[macro_use]
-extern crate nom;
+#[cfg(test)]
+extern crate proptest;
 #[cfg(feature = "rayon")]
 extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
 
+pub mod animation;
+mod aov;
+pub mod distributed;
+pub mod framebuffer;
 pub mod light;
 mod material;
+pub mod mesh;
 pub mod parser;
 mod ray;
 pub mod renderable;
 mod scene;
+#[cfg(unix)]
+pub mod server;
+mod stats;
 mod trace;
 pub mod util;
+mod validate;
 
-pub use material::Material;
+pub use animation::Animation;
+pub use aov::Aov;
+pub use framebuffer::Framebuffer;
+pub use material::{BackFace, Material, Sides};
+pub use mesh::Mesh;
 pub use ray::Ray;
 pub use scene::Scene;
+pub use stats::RenderStats;
+pub use validate::{Issue, Severity};
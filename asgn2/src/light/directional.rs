@@ -1,17 +1,20 @@
 use std::f32;
 
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector3};
 use image::Rgb;
 
 use light::Light;
+use util;
 
 /// A directional light.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Directional {
     /// The color of the light.
+    #[serde(with = "util::serde_rgb")]
     pub color: Rgb<f32>,
 
     /// The direction the light is pointing in.
+    #[serde(with = "util::serde_vector")]
     pub direction: Vector3<f32>,
 
     /// The intensity of the light.
@@ -24,7 +27,8 @@ impl Light for Directional {
     }
 
     fn direction_from(&self, _point: Vector3<f32>) -> (Vector3<f32>, f32) {
-        (self.direction, f32::INFINITY)
+        // The light shines in its direction, so comes from the opposite one.
+        (-self.direction.normalize(), f32::INFINITY)
     }
 
     fn intensity_at(&self, _point: Vector3<f32>) -> f32 {
@@ -16,7 +16,8 @@ pub use light::spot::Spot;
 /// Any light defined in this crate.
 ///
 /// Defined because it's more efficient than a trait object.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum DynamicLight {
     /// A directional light.
     Directional(Directional),
@@ -54,6 +55,46 @@ impl Light for DynamicLight {
     }
 }
 
+/// How the intensity of a point or spot light falls off with distance. At a
+/// distance `d`, the intensity is divided by
+/// `constant + linear * d + quadratic * d * d`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attenuation {
+    /// The constant coefficient.
+    pub constant: f32,
+
+    /// The coefficient of the distance.
+    pub linear: f32,
+
+    /// The coefficient of the square of the distance.
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    /// The physically correct inverse-square falloff.
+    pub const INVERSE_SQUARE: Attenuation = Attenuation {
+        constant: 0.0,
+        linear: 0.0,
+        quadratic: 1.0,
+    };
+
+    /// Returns the factor the intensity is multiplied by at a distance.
+    pub fn factor(&self, distance: f32) -> f32 {
+        (self.constant + self.linear * distance + self.quadratic * distance * distance).recip()
+    }
+}
+
+/// Inverse-square falloff, ten times brighter than `INVERSE_SQUARE`, which
+/// is what lights have always used.
+impl Default for Attenuation {
+    fn default() -> Attenuation {
+        Attenuation {
+            quadratic: 0.1,
+            ..Attenuation::INVERSE_SQUARE
+        }
+    }
+}
+
 /// A trait for lights.
 pub trait Light: Debug {
     /// Returns the color of the light.
@@ -1,19 +1,26 @@
 use cgmath::{InnerSpace, Vector3};
 use image::Rgb;
 
-use light::Light;
+use light::{Attenuation, Light};
+use util;
 
 /// A point light source.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     /// The color of the light.
+    #[serde(with = "util::serde_rgb")]
     pub color: Rgb<f32>,
 
     /// The position the light is emitted from.
+    #[serde(with = "util::serde_vector")]
     pub position: Vector3<f32>,
 
     /// The intensity of the light.
     pub intensity: f32,
+
+    /// How the intensity falls off with distance.
+    #[serde(default)]
+    pub attenuation: Attenuation,
 }
 
 impl Light for Point {
@@ -27,6 +34,6 @@ impl Light for Point {
     }
 
     fn intensity_at(&self, point: Vector3<f32>) -> f32 {
-        10.0 * self.intensity * (point - self.position).magnitude2().recip()
+        self.intensity * self.attenuation.factor((point - self.position).magnitude())
     }
 }
@@ -1,28 +1,39 @@
+use cgmath::num_traits::clamp;
 use cgmath::{InnerSpace, Vector3};
 use image::Rgb;
 
-use light::Light;
+use light::{Attenuation, Light};
+use util;
 
 /// A spotlight.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Spot {
     /// The color of the light.
+    #[serde(with = "util::serde_rgb")]
     pub color: Rgb<f32>,
 
     /// The position the light is emitted from.
+    #[serde(with = "util::serde_vector")]
     pub position: Vector3<f32>,
 
     /// The direction the light is pointing in.
+    #[serde(with = "util::serde_vector")]
     pub direction: Vector3<f32>,
 
     /// The intensity of the light.
     pub intensity: f32,
 
-    /// The angle at which the light starts falling off.
+    /// The angle from the light's direction, in degrees, at which the light
+    /// starts falling off.
     pub falloff_angle: f32,
 
-    /// The angle after which this light ceases to have an effect.
+    /// The angle from the light's direction, in degrees, after which this
+    /// light ceases to have an effect.
     pub max_angle: f32,
+
+    /// How the intensity falls off with distance.
+    #[serde(default)]
+    pub attenuation: Attenuation,
 }
 
 impl Light for Spot {
@@ -37,7 +48,19 @@ impl Light for Spot {
         (v.normalize(), v.magnitude())
     }
 
-    fn intensity_at(&self, _point: Vector3<f32>) -> f32 {
-        unimplemented!()
+    fn intensity_at(&self, point: Vector3<f32>) -> f32 {
+        let v = point - self.position;
+        let cos = self.direction.normalize().dot(v.normalize());
+        let angle = clamp(cos, -1.0, 1.0).acos().to_degrees();
+        // Inside the falloff angle, the light acts like a point light, and
+        // fades out linearly between it and the maximum angle.
+        let cone = if angle <= self.falloff_angle {
+            1.0
+        } else if angle >= self.max_angle {
+            0.0
+        } else {
+            (self.max_angle - angle) / (self.max_angle - self.falloff_angle)
+        };
+        cone * self.intensity * self.attenuation.factor(v.magnitude())
     }
 }
@@ -1,25 +1,103 @@
 use image::{Pixel, Rgb};
 
+use util;
+
 /// The material a Renderable is made of.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Material {
     /// The ambient color of the material.
+    #[serde(with = "util::serde_rgb")]
     pub ambient: Rgb<f32>,
 
     /// The diffuse color of the material.
+    #[serde(with = "util::serde_rgb")]
     pub diffuse: Rgb<f32>,
 
     /// The specular color of the material.
+    #[serde(with = "util::serde_rgb")]
     pub specular: Rgb<f32>,
 
     /// The phong cosine power for specular highlights.
     pub phong: f32,
 
     /// The transmissive color of the material.
+    #[serde(with = "util::serde_rgb")]
     pub transmissive: Rgb<f32>,
 
     /// The index of refraction of the material.
     pub ior: f32,
+
+    /// The color of the light the material gives off. Emissive triangles and
+    /// spheres light the rest of the scene; planes only glow.
+    #[serde(with = "util::serde_rgb")]
+    pub emissive: Rgb<f32>,
+
+    /// The intensity of the light the material gives off.
+    pub emissive_intensity: f32,
+
+    /// If set, shadows are tinted by the transmissive color once per this
+    /// distance travelled inside the object, rather than once per surface.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attenuation_distance: Option<f32>,
+
+    /// Which sides of triangles and planes, or of spheres, can be hit.
+    pub sides: Sides,
+
+    /// The appearance of the back of the surface, if it differs from the
+    /// front.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub back: Option<BackFace>,
+}
+
+impl Material {
+    /// Returns the material as seen from the front or the back of a surface.
+    pub fn side(&self, front: bool) -> Material {
+        match self.back {
+            Some(ref back) if !front => Material {
+                ambient: back.ambient,
+                diffuse: back.diffuse,
+                specular: back.specular,
+                phong: back.phong,
+                ..*self
+            },
+            _ => *self,
+        }
+    }
+}
+
+/// Which sides of a surface can be hit. The front of a surface is the side
+/// its normal points out of; for spheres, that's the outside.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sides {
+    /// Both sides can be hit. The back is lit as if the normal were flipped
+    /// to face it.
+    #[default]
+    Both,
+
+    /// Only the front can be hit; rays pass through the back.
+    Front,
+}
+
+/// The appearance of the back of a surface. The transmissive and emissive
+/// colors and index of refraction are shared with the front.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackFace {
+    /// The ambient color of the back.
+    #[serde(with = "util::serde_rgb")]
+    pub ambient: Rgb<f32>,
+
+    /// The diffuse color of the back.
+    #[serde(with = "util::serde_rgb")]
+    pub diffuse: Rgb<f32>,
+
+    /// The specular color of the back.
+    #[serde(with = "util::serde_rgb")]
+    pub specular: Rgb<f32>,
+
+    /// The phong cosine power for specular highlights on the back.
+    pub phong: f32,
 }
 
 impl Default for Material {
@@ -31,6 +109,11 @@ impl Default for Material {
             phong: 5.0,
             transmissive: Pixel::from_channels(0.0, 0.0, 0.0, 1.0),
             ior: 1.0,
+            emissive: Pixel::from_channels(0.0, 0.0, 0.0, 1.0),
+            emissive_intensity: 1.0,
+            attenuation_distance: None,
+            sides: Sides::Both,
+            back: None,
         }
     }
 }
@@ -0,0 +1,247 @@
+//! Triangle meshes, and loading them from PLY and STL files.
+//!
+//! Meshes are loaded into a `Mesh`, which can be added to a scene's `meshes`
+//! as is. In scene files, the `mesh PATH` directive does this.
+
+mod ply;
+mod stl;
+#[cfg(test)]
+mod tests;
+
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult};
+use std::path::Path;
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Vector3};
+use float_ord::FloatOrd;
+use image::Rgb;
+
+use material::{Material, Sides};
+use parser::math;
+use ray::Ray;
+use renderable::{intersect_triangle, Triangle};
+
+/// A triangle mesh.
+///
+/// Its triangles share their vertices, and refer to their material by its
+/// index in the scene's `materials`, so each takes up several times less
+/// memory than a separate `Triangle`. Its vertices and faces are shared
+/// between clones of the mesh too, until one of them is changed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mesh {
+    /// The positions of the vertices.
+    #[serde(with = "triples")]
+    pub positions: Arc<Vec<Vector3<f32>>>,
+
+    /// The normals of the vertices, if the file has them.
+    #[serde(skip_serializing_if = "Option::is_none", with = "triples::option")]
+    pub normals: Option<Arc<Vec<Vector3<f32>>>>,
+
+    /// The colors of the vertices, if the file has them. If a mesh in a scene
+    /// has colors, each triangle's diffuse color is the average of its
+    /// vertices' colors.
+    #[serde(skip_serializing_if = "Option::is_none", with = "triples::option")]
+    pub colors: Option<Arc<Vec<Rgb<f32>>>>,
+
+    /// The indices of the vertices of each triangle. Polygons with more
+    /// vertices are split into triangles when they're loaded.
+    pub faces: Arc<Vec<[u32; 3]>>,
+
+    /// The index of the mesh's material in the scene's `materials`. It's 0
+    /// for meshes loaded from files.
+    pub material: usize,
+}
+
+impl Mesh {
+    /// Loads a mesh from a PLY or STL file, picking the format by the file's
+    /// extension.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> IoResult<Mesh> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        let parse = match ext.as_deref() {
+            Some("ply") => Mesh::from_ply,
+            Some("stl") => Mesh::from_stl,
+            _ => {
+                let msg = "meshes must be .ply or .stl files";
+                return Err(IoError::new(ErrorKind::InvalidInput, msg));
+            }
+        };
+        let mut buf = Vec::new();
+        File::open(path)?.read_to_end(&mut buf)?;
+        parse(&buf)
+    }
+
+    /// Parses a mesh in the PLY format, in ASCII or either binary encoding.
+    ///
+    /// The vertices are read from the `x`, `y` and `z` properties of the
+    /// `vertex` element, along with its `nx`, `ny` and `nz` normals and its
+    /// `red`, `green` and `blue` colors if it has them. Colors stored as
+    /// integers are taken to range up to 255. The faces are read from the
+    /// `vertex_indices` (or `vertex_index`) property of the `face` element.
+    /// Other elements and properties are skipped.
+    pub fn from_ply(data: &[u8]) -> IoResult<Mesh> {
+        ply::parse(data)
+    }
+
+    /// Parses a mesh in the STL format, in ASCII or binary. STL files only
+    /// have a normal for each facet, which is used as the normal of each of
+    /// its vertices, unless every facet's normal is zero. They don't have
+    /// colors.
+    pub fn from_stl(data: &[u8]) -> IoResult<Mesh> {
+        stl::parse(data)
+    }
+
+    /// Returns the triangle at the given index in `faces`, made of the given
+    /// material, which should be the mesh's.
+    ///
+    /// Like those from `normal_triangle` directives, the triangle's normal is
+    /// the sum of its vertices' normals, if the mesh has them. If the mesh has
+    /// colors, the triangle's diffuse color is the average of its vertices'.
+    pub fn triangle(&self, face: usize, material: Material) -> Triangle {
+        let [i, j, k] = self.faces[face];
+        let (i, j, k) = (i as usize, j as usize, k as usize);
+        let mut material = material;
+        if let Some(ref colors) = self.colors {
+            let channel =
+                |ch: usize| (colors[i].data[ch] + colors[j].data[ch] + colors[k].data[ch]) / 3.0;
+            material.diffuse = Rgb {
+                data: [channel(0), channel(1), channel(2)],
+            };
+        }
+        Triangle {
+            vertices: self.vertices(face),
+            normal: self.normal(face),
+            material,
+        }
+    }
+
+    /// Returns all the triangles of the mesh, made of the given material.
+    pub fn triangles(&self, material: Material) -> Vec<Triangle> {
+        (0..self.faces.len())
+            .map(|face| self.triangle(face, material))
+            .collect()
+    }
+
+    /// Returns the index in `faces` of the closest triangle the ray collides
+    /// with, and the distance to it. Only the fronts of the triangles can be
+    /// hit if `sides` is `Front`.
+    pub fn collides_with(&self, ray: Ray, sides: Sides) -> Option<(usize, f32)> {
+        (0..self.faces.len())
+            .filter(|&face| {
+                let dot = self.normal(face).dot(ray.direction);
+                sides == Sides::Both || dot < 0.0 || dot.is_nan()
+            })
+            .filter_map(|face| intersect_triangle(self.vertices(face), ray).map(|d| (face, d)))
+            .min_by_key(|&(_, d)| FloatOrd(d))
+    }
+
+    /// Returns the positions of the vertices of a triangle.
+    pub(crate) fn vertices(&self, face: usize) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        let [i, j, k] = self.faces[face];
+        let p = &self.positions;
+        (p[i as usize], p[j as usize], p[k as usize])
+    }
+
+    /// Returns the normal of a triangle, which isn't normalized.
+    pub(crate) fn normal(&self, face: usize) -> Vector3<f32> {
+        let (a, b, c) = self.vertices(face);
+        match self.normals {
+            Some(ref n) => {
+                let [i, j, k] = self.faces[face];
+                let (i, j, k) = (i as usize, j as usize, k as usize);
+                math::normal_from_normals(a, b, c, n[i], n[j], n[k])
+            }
+            None => math::normal_from_points(a, b, c),
+        }
+    }
+}
+
+/// Returns an error for a malformed mesh file.
+fn invalid(msg: String) -> IoError {
+    IoError::new(ErrorKind::InvalidData, msg)
+}
+
+/// Serializes shared lists of vectors or colors as lists of `[x, y, z]` or
+/// `[r, g, b]`, for use with `#[serde(with)]`.
+mod triples {
+    use std::sync::Arc;
+
+    use cgmath::Vector3;
+    use image::Rgb;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// A vector or color.
+    pub trait Triple: Sized {
+        fn to_array(&self) -> [f32; 3];
+        fn from_array(a: [f32; 3]) -> Self;
+    }
+
+    impl Triple for Vector3<f32> {
+        fn to_array(&self) -> [f32; 3] {
+            [self.x, self.y, self.z]
+        }
+        fn from_array(a: [f32; 3]) -> Self {
+            a.into()
+        }
+    }
+
+    impl Triple for Rgb<f32> {
+        fn to_array(&self) -> [f32; 3] {
+            self.data
+        }
+        fn from_array(data: [f32; 3]) -> Self {
+            Rgb { data }
+        }
+    }
+
+    /// Serializes a list without copying it.
+    struct Seq<'a, T: 'a>(&'a [T]);
+
+    impl<'a, T: Triple> Serialize for Seq<'a, T> {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.collect_seq(self.0.iter().map(Triple::to_array))
+        }
+    }
+
+    pub fn serialize<T: Triple, S: Serializer>(v: &[T], s: S) -> Result<S::Ok, S::Error> {
+        Seq(v).serialize(s)
+    }
+
+    pub fn deserialize<'de, T: Triple, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Arc<Vec<T>>, D::Error> {
+        let arrays = Vec::<[f32; 3]>::deserialize(d)?;
+        Ok(Arc::new(arrays.into_iter().map(T::from_array).collect()))
+    }
+
+    /// The same, for optional lists.
+    pub mod option {
+        use std::sync::Arc;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        use super::{Seq, Triple};
+
+        pub fn serialize<T: Triple, S: Serializer>(
+            v: &Option<Arc<Vec<T>>>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            match *v {
+                Some(ref v) => s.serialize_some(&Seq(v)),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, T: Triple, D: Deserializer<'de>>(
+            d: D,
+        ) -> Result<Option<Arc<Vec<T>>>, D::Error> {
+            let arrays = Option::<Vec<[f32; 3]>>::deserialize(d)?;
+            Ok(arrays.map(|a| Arc::new(a.into_iter().map(T::from_array).collect())))
+        }
+    }
+}
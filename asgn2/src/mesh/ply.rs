@@ -0,0 +1,315 @@
+use std::io::Result as IoResult;
+use std::str::{self, SplitAsciiWhitespace};
+use std::sync::Arc;
+
+use cgmath::Vector3;
+use image::Rgb;
+
+use mesh::{invalid, Mesh};
+
+/// The type of a property's values.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn from_name(name: &str) -> IoResult<Type> {
+        Ok(match name {
+            "char" | "int8" => Type::I8,
+            "uchar" | "uint8" => Type::U8,
+            "short" | "int16" => Type::I16,
+            "ushort" | "uint16" => Type::U16,
+            "int" | "int32" => Type::I32,
+            "uint" | "uint32" => Type::U32,
+            "float" | "float32" => Type::F32,
+            "double" | "float64" => Type::F64,
+            _ => return Err(invalid(format!("unknown property type `{}`", name))),
+        })
+    }
+
+    /// Returns the size of a value of the type in the binary encodings.
+    fn size(self) -> usize {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        }
+    }
+}
+
+/// A property of an element.
+#[derive(Debug)]
+struct Property {
+    name: String,
+
+    /// The type of the property's values.
+    ty: Type,
+
+    /// For list properties, the type of the number of values in the list.
+    count: Option<Type>,
+}
+
+/// A kind of element, and the number of them in the file.
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    /// Returns the index of the scalar property with the given name.
+    fn scalar(&self, name: &str) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| p.name == name && p.count.is_none())
+    }
+
+    /// Returns the indices of the scalar properties with the given names, if
+    /// the element has all of them.
+    fn scalars(&self, names: [&str; 3]) -> Option<[usize; 3]> {
+        Some([
+            self.scalar(names[0])?,
+            self.scalar(names[1])?,
+            self.scalar(names[2])?,
+        ])
+    }
+}
+
+/// The values in the body of the file, after the header.
+enum Values<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary { data: &'a [u8], big_endian: bool },
+}
+
+impl<'a> Values<'a> {
+    /// Reads the next value, which is of the given type.
+    fn next(&mut self, ty: Type) -> IoResult<f64> {
+        let eof = || invalid("unexpected end of file".to_string());
+        match *self {
+            Values::Ascii(ref mut tokens) => {
+                let token = tokens.next().ok_or_else(eof)?;
+                token
+                    .parse()
+                    .map_err(|_| invalid(format!("`{}` isn't a number", token)))
+            }
+            Values::Binary {
+                ref mut data,
+                big_endian,
+            } => {
+                let size = ty.size();
+                if data.len() < size {
+                    return Err(eof());
+                }
+                let mut b = [0; 8];
+                b[..size].copy_from_slice(&data[..size]);
+                *data = &data[size..];
+                if big_endian {
+                    b[..size].reverse();
+                }
+                Ok(match ty {
+                    Type::I8 => f64::from(b[0] as i8),
+                    Type::U8 => f64::from(b[0]),
+                    Type::I16 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+                    Type::U16 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+                    Type::I32 => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Type::U32 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Type::F32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Type::F64 => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+
+    /// Reads the number of values in a list, or a vertex index, which must
+    /// be a non-negative integer.
+    fn next_index(&mut self, ty: Type) -> IoResult<usize> {
+        let n = self.next(ty)?;
+        if n >= 0.0 && n.fract() == 0.0 && n <= u32::MAX as f64 {
+            Ok(n as usize)
+        } else {
+            Err(invalid(format!("{} isn't a valid count or index", n)))
+        }
+    }
+}
+
+pub fn parse(data: &[u8]) -> IoResult<Mesh> {
+    let (elements, mut values) = parse_header(data)?;
+    let vertex_count = elements
+        .iter()
+        .find(|e| e.name == "vertex")
+        .ok_or_else(|| invalid("the file has no vertex element".to_string()))?
+        .count;
+
+    let mut positions = Vec::new();
+    let mut normals = None;
+    let mut colors = None;
+    let mut faces = Vec::new();
+    for element in &elements {
+        let position_props = element.scalars(["x", "y", "z"]);
+        let normal_props = element.scalars(["nx", "ny", "nz"]);
+        let color_props = element.scalars(["red", "green", "blue"]);
+        let indices = element.properties.iter().position(|p| {
+            p.count.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index")
+        });
+        let (is_vertex, is_face) = (element.name == "vertex", element.name == "face");
+        if is_vertex {
+            if position_props.is_none() {
+                return Err(invalid("vertices need x, y and z properties".to_string()));
+            }
+            // Each vertex takes up at least a byte, which limits how many
+            // there can be.
+            let reserve = element.count.min(data.len());
+            positions.reserve(reserve);
+            if normal_props.is_some() {
+                normals = Some(Vec::with_capacity(reserve));
+            }
+            if color_props.is_some() {
+                colors = Some(Vec::with_capacity(reserve));
+            }
+        } else if is_face && indices.is_none() {
+            return Err(invalid("faces need a vertex_indices property".to_string()));
+        }
+
+        let mut row = vec![0.0; element.properties.len()];
+        let mut list = Vec::new();
+        for _ in 0..element.count {
+            list.clear();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.count {
+                    Some(count) => {
+                        let n = values.next_index(count)?;
+                        let keep = is_face && Some(i) == indices;
+                        for _ in 0..n {
+                            if keep {
+                                list.push(values.next_index(property.ty)? as u32);
+                            } else {
+                                values.next(property.ty)?;
+                            }
+                        }
+                    }
+                    None => row[i] = values.next(property.ty)?,
+                }
+            }
+
+            let vector =
+                |[x, y, z]: [usize; 3]| Vector3::new(row[x] as f32, row[y] as f32, row[z] as f32);
+            if let Some(p) = position_props.filter(|_| is_vertex) {
+                positions.push(vector(p));
+                if let (Some(normals), Some(n)) = (normals.as_mut(), normal_props) {
+                    normals.push(vector(n));
+                }
+                if let (Some(colors), Some(c)) = (colors.as_mut(), color_props) {
+                    // Integer colors range up to 255; floating-point ones up
+                    // to 1.
+                    let scale = match element.properties[c[0]].ty {
+                        Type::F32 | Type::F64 => 1.0,
+                        _ => 1.0 / 255.0,
+                    };
+                    let channel = |i: usize| (row[c[i]] * scale) as f32;
+                    colors.push(Rgb {
+                        data: [channel(0), channel(1), channel(2)],
+                    });
+                }
+            } else if is_face {
+                if let Some(&i) = list.iter().find(|&&i| i as usize >= vertex_count) {
+                    return Err(invalid(format!(
+                        "a face uses vertex {}, which doesn't exist",
+                        i
+                    )));
+                }
+                // Polygons are split into a fan of triangles.
+                for j in 2..list.len() {
+                    faces.push([list[0], list[j - 1], list[j]]);
+                }
+            }
+        }
+    }
+    Ok(Mesh {
+        positions: Arc::new(positions),
+        normals: normals.map(Arc::new),
+        colors: colors.map(Arc::new),
+        faces: Arc::new(faces),
+        material: 0,
+    })
+}
+
+/// Parses the header of the file, returning its elements and the values in
+/// the rest of it.
+fn parse_header(data: &[u8]) -> IoResult<(Vec<Element>, Values<'_>)> {
+    let mut elements: Vec<Element> = Vec::new();
+    let mut encoding = None;
+    let mut rest = data;
+    for line_number in 0.. {
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("the header doesn't end".to_string()))?;
+        let line = str::from_utf8(&rest[..end])
+            .map_err(|_| invalid("the header isn't valid text".to_string()))?;
+        rest = &rest[end + 1..];
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        match words[..] {
+            ["ply"] if line_number == 0 => {}
+            _ if line_number == 0 => return Err(invalid("not a PLY file".to_string())),
+            ["format", format, _] => encoding = Some(format.to_string()),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("bad element count `{}`", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", ..] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("a property comes before any element".to_string()))?;
+                element.properties.push(match words[1..] {
+                    ["list", count, ty, name] => Property {
+                        name: name.to_string(),
+                        ty: Type::from_name(ty)?,
+                        count: Some(Type::from_name(count)?),
+                    },
+                    [ty, name] => Property {
+                        name: name.to_string(),
+                        ty: Type::from_name(ty)?,
+                        count: None,
+                    },
+                    _ => return Err(invalid(format!("bad property `{}`", line.trim()))),
+                });
+            }
+            ["end_header"] => break,
+            _ => return Err(invalid(format!("bad header line `{}`", line.trim()))),
+        }
+    }
+
+    let values = match encoding.as_deref() {
+        Some("ascii") => {
+            let text = str::from_utf8(rest)
+                .map_err(|_| invalid("the file isn't valid text".to_string()))?;
+            Values::Ascii(text.split_ascii_whitespace())
+        }
+        Some("binary_little_endian") => Values::Binary {
+            data: rest,
+            big_endian: false,
+        },
+        Some("binary_big_endian") => Values::Binary {
+            data: rest,
+            big_endian: true,
+        },
+        Some(e) => return Err(invalid(format!("unknown format `{}`", e))),
+        None => return Err(invalid("the header has no format".to_string())),
+    };
+    Ok((elements, values))
+}
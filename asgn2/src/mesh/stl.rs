@@ -0,0 +1,132 @@
+use std::io::Result as IoResult;
+use std::iter::Peekable;
+use std::str::{self, SplitAsciiWhitespace};
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Vector3};
+
+use mesh::{invalid, Mesh};
+
+/// The size of the header of a binary STL file, up to the number of facets.
+const HEADER_SIZE: usize = 84;
+
+/// The size of each facet in a binary STL file.
+const FACET_SIZE: usize = 50;
+
+pub fn parse(data: &[u8]) -> IoResult<Mesh> {
+    // Binary files are supposed to not start with "solid", as ASCII ones do,
+    // but plenty do anyway, so a file whose size is right for a binary file
+    // is taken to be one.
+    let binary_size = data
+        .get(80..HEADER_SIZE)
+        .map(|n| HEADER_SIZE as u64 + FACET_SIZE as u64 * u64::from(read_u32(n)));
+    if !data.starts_with(b"solid") || binary_size == Some(data.len() as u64) {
+        parse_binary(data)
+    } else {
+        parse_ascii(data)
+    }
+}
+
+fn parse_binary(data: &[u8]) -> IoResult<Mesh> {
+    let eof = || invalid("unexpected end of file".to_string());
+    let count = read_u32(data.get(80..HEADER_SIZE).ok_or_else(eof)?) as usize;
+    let facets = &data[HEADER_SIZE..];
+    if facets.len() / FACET_SIZE < count {
+        return Err(eof());
+    } else if count > u32::MAX as usize / 3 {
+        // Each facet has its own three vertices, which must be indexable.
+        return Err(invalid("the file has too many facets".to_string()));
+    }
+
+    let mut mesh = Mesh::default();
+    for facet in facets.chunks(FACET_SIZE).take(count) {
+        let vector = |i: usize| {
+            let f = |j: usize| f32::from_bits(read_u32(&facet[i * 12 + j * 4..]));
+            Vector3::new(f(0), f(1), f(2))
+        };
+        add_facet(&mut mesh, vector(0), [vector(1), vector(2), vector(3)]);
+    }
+    Ok(mesh)
+}
+
+fn parse_ascii(data: &[u8]) -> IoResult<Mesh> {
+    let text =
+        str::from_utf8(data).map_err(|_| invalid("the file isn't valid text".to_string()))?;
+    let mut tokens = text.split_ascii_whitespace().peekable();
+    expect(&mut tokens, "solid")?;
+    // Skip the name of the solid, which may contain spaces.
+    while tokens
+        .peek()
+        .is_some_and(|&t| t != "facet" && t != "endsolid")
+    {
+        tokens.next();
+    }
+
+    let mut mesh = Mesh::default();
+    loop {
+        match tokens.next() {
+            Some("facet") => {
+                expect(&mut tokens, "normal")?;
+                let normal = vector(&mut tokens)?;
+                expect(&mut tokens, "outer")?;
+                expect(&mut tokens, "loop")?;
+                let mut vertex = || {
+                    expect(&mut tokens, "vertex")?;
+                    vector(&mut tokens)
+                };
+                let vertices = [vertex()?, vertex()?, vertex()?];
+                expect(&mut tokens, "endloop")?;
+                expect(&mut tokens, "endfacet")?;
+                add_facet(&mut mesh, normal, vertices);
+            }
+            Some("endsolid") | None => return Ok(mesh),
+            Some(t) => return Err(invalid(format!("expected `facet`, found `{}`", t))),
+        }
+    }
+}
+
+/// Adds a facet to the mesh, as three new vertices. Many files leave the
+/// normals of facets zero, in which case the normal of the plane the
+/// vertices are in is used instead.
+fn add_facet(mesh: &mut Mesh, normal: Vector3<f32>, vertices: [Vector3<f32>; 3]) {
+    let [a, b, c] = vertices;
+    let normal = if normal.magnitude2() > 0.0 {
+        normal
+    } else {
+        (b - a).cross(c - a)
+    };
+    let n = mesh.positions.len() as u32;
+    Arc::make_mut(&mut mesh.positions).extend_from_slice(&vertices);
+    Arc::make_mut(mesh.normals.get_or_insert_with(Default::default))
+        .extend_from_slice(&[normal; 3]);
+    Arc::make_mut(&mut mesh.faces).push([n, n + 1, n + 2]);
+}
+
+/// Reads the next token, which must be the given keyword.
+fn expect(tokens: &mut Peekable<SplitAsciiWhitespace>, keyword: &str) -> IoResult<()> {
+    match tokens.next() {
+        Some(t) if t == keyword => Ok(()),
+        Some(t) => Err(invalid(format!("expected `{}`, found `{}`", keyword, t))),
+        None => Err(invalid(format!(
+            "expected `{}`, found the end of the file",
+            keyword
+        ))),
+    }
+}
+
+/// Reads a vector, as three numbers.
+fn vector(tokens: &mut Peekable<SplitAsciiWhitespace>) -> IoResult<Vector3<f32>> {
+    let mut number = || -> IoResult<f32> {
+        let t = tokens
+            .next()
+            .ok_or_else(|| invalid("expected a number, found the end of the file".to_string()))?;
+        t.parse()
+            .map_err(|_| invalid(format!("expected a number, found `{}`", t)))
+    };
+    Ok(Vector3::new(number()?, number()?, number()?))
+}
+
+/// Reads a little-endian `u32` from the start of a slice.
+fn read_u32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
@@ -0,0 +1,251 @@
+use std::sync::Arc;
+
+use cgmath::Vector3;
+use image::Rgb;
+
+use material::Material;
+use mesh::Mesh;
+
+/// A square and a triangle, with normals and colors, as an ASCII PLY file.
+/// The file has an extra element, and extra properties, to skip.
+const ASCII_PLY: &str = "ply
+format ascii 1.0
+comment made by hand
+element vertex 5
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+property uchar alpha
+element face 2
+property list uchar int vertex_indices
+property int flags
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 0 0 1 255 0 0 255
+1 0 0 0 0 1 0 255 0 255
+1 1 0 0 0 1 0 0 255 255
+0 1 0 0 0 1 255 255 255 255
+0 0 1 1 0 0 0 0 0 255
+4 0 1 2 3 7
+3 0 3 4 7
+0 1
+";
+
+fn expected_ply() -> Mesh {
+    let v = Vector3::new;
+    let z = v(0.0, 0.0, 1.0);
+    let rgb = |r, g, b| Rgb { data: [r, g, b] };
+    Mesh {
+        positions: Arc::new(vec![
+            v(0.0, 0.0, 0.0),
+            v(1.0, 0.0, 0.0),
+            v(1.0, 1.0, 0.0),
+            v(0.0, 1.0, 0.0),
+            v(0.0, 0.0, 1.0),
+        ]),
+        normals: Some(Arc::new(vec![z, z, z, z, v(1.0, 0.0, 0.0)])),
+        colors: Some(Arc::new(vec![
+            rgb(1.0, 0.0, 0.0),
+            rgb(0.0, 1.0, 0.0),
+            rgb(0.0, 0.0, 1.0),
+            rgb(1.0, 1.0, 1.0),
+            rgb(0.0, 0.0, 0.0),
+        ])),
+        faces: Arc::new(vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]),
+        material: 0,
+    }
+}
+
+#[test]
+fn ascii_ply() {
+    assert_eq!(
+        Mesh::from_ply(ASCII_PLY.as_bytes()).unwrap(),
+        expected_ply()
+    );
+}
+
+#[test]
+fn binary_ply() {
+    for &big_endian in &[false, true] {
+        let format = if big_endian { "big" } else { "little" };
+        let mut data = format!(
+            "ply\nformat binary_{}_endian 1.0\nelement vertex 5\nproperty double x\n\
+             property double y\nproperty double z\nproperty float nx\nproperty float ny\n\
+             property float nz\nproperty uchar red\nproperty uchar green\n\
+             property uchar blue\nelement face 2\nproperty list uchar uint vertex_indices\n\
+             end_header\n",
+            format
+        )
+        .into_bytes();
+        let mut push = |bytes: &mut [u8]| {
+            if big_endian {
+                bytes.reverse();
+            }
+            data.extend_from_slice(bytes);
+        };
+
+        let mesh = expected_ply();
+        for i in 0..5 {
+            let (p, n, c) = (
+                mesh.positions[i],
+                mesh.normals.as_ref().unwrap()[i],
+                mesh.colors.as_ref().unwrap()[i],
+            );
+            for &x in &[p.x, p.y, p.z] {
+                push(&mut f64::from(x).to_le_bytes());
+            }
+            for &x in &[n.x, n.y, n.z] {
+                push(&mut x.to_le_bytes());
+            }
+            for &x in &c.data {
+                push(&mut [(x * 255.0) as u8]);
+            }
+        }
+        for face in &[&[0u32, 1, 2, 3][..], &[0, 3, 4]] {
+            push(&mut [face.len() as u8]);
+            for &i in face.iter() {
+                push(&mut i.to_le_bytes());
+            }
+        }
+
+        assert_eq!(Mesh::from_ply(&data).unwrap(), mesh);
+        assert!(Mesh::from_ply(&data[..data.len() - 1]).is_err());
+    }
+}
+
+#[test]
+fn bad_ply() {
+    let bad = |input: &str| Mesh::from_ply(input.as_bytes()).unwrap_err().to_string();
+    let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+                  property float y\nproperty float z\nelement face 1\n\
+                  property list uchar int vertex_indices\nend_header\n";
+    let vertices = "0 0 0\n1 0 0\n0 1 0\n";
+    assert!(Mesh::from_ply(format!("{}{}3 0 1 2\n", header, vertices).as_bytes()).is_ok());
+    assert_eq!(
+        bad(&format!("{}{}3 0 1 3\n", header, vertices)),
+        "a face uses vertex 3, which doesn't exist"
+    );
+    assert_eq!(
+        bad(&format!("{}{}3 0 1\n", header, vertices)),
+        "unexpected end of file"
+    );
+    assert_eq!(
+        bad(&format!("{}{}3 0 1 -2\n", header, vertices)),
+        "-2 isn't a valid count or index"
+    );
+    assert_eq!(bad("ply\nformat ascii 1.0\n"), "the header doesn't end");
+    assert_eq!(bad("obj\n"), "not a PLY file");
+}
+
+/// A tetrahedron with one facet's normal left zero, as an ASCII STL file.
+const ASCII_STL: &str = "solid a tetrahedron
+facet normal 0 0 -1
+  outer loop
+    vertex 0 0 0
+    vertex 0 1 0
+    vertex 1 0 0
+  endloop
+endfacet
+facet normal 0 0 0
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 0 0 1
+  endloop
+endfacet
+endsolid a tetrahedron
+";
+
+#[test]
+fn stl() {
+    let v = Vector3::new;
+    let expected = Mesh {
+        positions: Arc::new(vec![
+            v(0.0, 0.0, 0.0),
+            v(0.0, 1.0, 0.0),
+            v(1.0, 0.0, 0.0),
+            v(0.0, 0.0, 0.0),
+            v(1.0, 0.0, 0.0),
+            v(0.0, 0.0, 1.0),
+        ]),
+        normals: Some(Arc::new(vec![
+            v(0.0, 0.0, -1.0),
+            v(0.0, 0.0, -1.0),
+            v(0.0, 0.0, -1.0),
+            v(0.0, -1.0, 0.0),
+            v(0.0, -1.0, 0.0),
+            v(0.0, -1.0, 0.0),
+        ])),
+        colors: None,
+        faces: Arc::new(vec![[0, 1, 2], [3, 4, 5]]),
+        material: 0,
+    };
+    assert_eq!(Mesh::from_stl(ASCII_STL.as_bytes()).unwrap(), expected);
+
+    // Binary files may start with "solid" too.
+    for header in &[&b"binary"[..], b"solid but binary"] {
+        let mut data = header.to_vec();
+        data.resize(80, 0);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for facet in expected.positions.chunks(3) {
+            let normal = [0.0f32; 3];
+            let coords = facet.iter().flat_map(|p| vec![p.x, p.y, p.z]);
+            for x in normal.iter().cloned().chain(coords) {
+                data.extend_from_slice(&x.to_le_bytes());
+            }
+            data.extend_from_slice(&[0, 0]);
+        }
+        assert_eq!(Mesh::from_stl(&data).unwrap(), expected);
+    }
+
+    let truncated = &ASCII_STL[..ASCII_STL.find("endloop").unwrap()];
+    assert_eq!(
+        Mesh::from_stl(truncated.as_bytes())
+            .unwrap_err()
+            .to_string(),
+        "expected `endloop`, found the end of the file"
+    );
+}
+
+#[test]
+fn triangles() {
+    let mesh = expected_ply();
+    let material = Material::default();
+    let plain = Mesh {
+        colors: None,
+        ..mesh.clone()
+    };
+    let triangles = plain.triangles(material);
+    assert_eq!(triangles.len(), 3);
+    assert_eq!(
+        triangles[2].vertices,
+        (mesh.positions[0], mesh.positions[3], mesh.positions[4])
+    );
+    assert_eq!(triangles[0].normal, Vector3::new(0.0, 0.0, 3.0));
+    assert_eq!(triangles[0].material, material);
+
+    let colored = mesh.triangles(material);
+    assert_eq!(colored[0].material.diffuse.data, [1.0 / 3.0; 3]);
+    assert_eq!(
+        colored[2].material.diffuse.data,
+        [2.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]
+    );
+
+    // Without normals, they're found from the vertices.
+    let flat = Mesh {
+        normals: None,
+        ..plain
+    };
+    assert_eq!(
+        flat.triangles(material)[0].normal,
+        Vector3::new(0.0, 0.0, 1.0)
+    );
+}
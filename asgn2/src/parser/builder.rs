@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use cgmath::num_traits::clamp;
+use cgmath::{InnerSpace, Vector3};
+use image::Rgb;
+
+use light::{Directional, DynamicLight, Point, Spot};
+use material::{BackFace, Material, Sides};
+use mesh::Mesh;
+use parser::{Line, ParseErrorKind};
+use renderable::{DynamicRenderable, Plane, Sphere};
+use scene::Scene;
+
+/// Builds up a scene from its lines, one at a time.
+#[derive(Debug, Default)]
+pub struct SceneBuilder {
+    /// The scene so far.
+    pub scene: Scene,
+
+    /// The vertices defined so far.
+    vertices: Vec<Vector3<f32>>,
+
+    /// The normals defined so far.
+    normals: Vec<Vector3<f32>>,
+
+    /// The maximum number of vertices, from the `max_vertices` directive.
+    max_vertices: Option<usize>,
+
+    /// The maximum number of normals, from the `max_normals` directive.
+    max_normals: Option<usize>,
+
+    /// The current material.
+    material: Material,
+
+    /// Whether the colors of meshes' vertices are used as their diffuse
+    /// colors, from the `vertex_colors` directive.
+    vertex_colors: bool,
+
+    /// The offset and scale meshes are moved by, from the `mesh_transform`
+    /// directive.
+    mesh_transform: Option<(Vector3<f32>, f32)>,
+
+    /// The run of triangles from `triangle` or `normal_triangle` directives
+    /// being gathered into a mesh, if any.
+    run: Option<TriangleRun>,
+}
+
+/// A run of triangles from `triangle` or `normal_triangle` directives, which
+/// are stored as a mesh rather than as separate objects.
+#[derive(Debug, Default)]
+struct TriangleRun {
+    /// The positions of the mesh's vertices.
+    positions: Vec<Vector3<f32>>,
+
+    /// The normals of the mesh's vertices, if the triangles are from
+    /// `normal_triangle` directives.
+    normals: Option<Vec<Vector3<f32>>>,
+
+    /// The indices of the vertices of each triangle.
+    faces: Vec<[u32; 3]>,
+
+    /// The index in the mesh of each vertex used so far, by the indices of
+    /// the vertex and normal it's made of.
+    indices: HashMap<(usize, Option<usize>), u32>,
+}
+
+impl SceneBuilder {
+    /// Applies a line to the scene.
+    pub fn apply(&mut self, line: Line) -> Result<(), ParseErrorKind> {
+        // A run of triangles ends at any other directive that isn't just
+        // defining the vertices or normals for them.
+        match line {
+            Line::Triangle(..)
+            | Line::NormalTriangle(..)
+            | Line::Vertex(..)
+            | Line::Normal(..)
+            | Line::MaxVertices(_)
+            | Line::MaxNormals(_) => {}
+            _ => self.end_run(),
+        }
+
+        match line {
+            Line::AmbientLight(r, g, b) => self.scene.ambient_light = convert_color(r, g, b),
+            Line::Background(r, g, b) => self.scene.background = convert_color(r, g, b),
+            Line::TransparentBackground => self.scene.transparent_background = true,
+            Line::Camera(px, py, pz, dx, dy, dz, ux, uy, uz, ha) => {
+                self.scene.camera_position = Vector3::new(px, py, pz);
+                self.scene.camera_direction = Vector3::new(dx, dy, dz).normalize();
+                self.scene.camera_up = Vector3::new(ux, uy, uz).normalize();
+                self.scene.camera_half_angle_tan = ha.to_radians().tan();
+            }
+            Line::DirectionalLight(r, g, b, x, y, z, i) => {
+                self.scene
+                    .lights
+                    .push(DynamicLight::Directional(Directional {
+                        color: convert_color(r, g, b),
+                        direction: Vector3::new(x, y, z),
+                        intensity: i,
+                    }));
+            }
+            Line::Material(ar, ag, ab, dr, dg, db, sr, sg, sb, ns, tr, tg, tb, ior) => {
+                self.material = Material {
+                    ambient: convert_color(ar, ag, ab),
+                    diffuse: convert_color(dr, dg, db),
+                    specular: convert_color(sr, sg, sb),
+                    phong: ns,
+                    transmissive: convert_color(tr, tg, tb),
+                    ior,
+                    ..Material::default()
+                };
+                self.vertex_colors = false;
+            }
+            Line::BackMaterial(ar, ag, ab, dr, dg, db, sr, sg, sb, ns) => {
+                self.material.back = Some(BackFace {
+                    ambient: convert_color(ar, ag, ab),
+                    diffuse: convert_color(dr, dg, db),
+                    specular: convert_color(sr, sg, sb),
+                    phong: ns,
+                });
+            }
+            Line::SingleSided => self.material.sides = Sides::Front,
+            Line::AttenuationDistance(d) => self.material.attenuation_distance = Some(d),
+            Line::Emissive(r, g, b, i) => {
+                self.material.emissive = convert_color(r, g, b);
+                self.material.emissive_intensity = i;
+            }
+            Line::VertexColors => self.vertex_colors = true,
+            Line::MeshTransform(x, y, z, s) => {
+                self.mesh_transform = Some((Vector3::new(x, y, z), s));
+            }
+            Line::MaxDepth(n) => self.scene.max_collisions = n,
+            Line::Samples(n) => self.scene.samples = n,
+            Line::LightSamples(n) => self.scene.light_samples = n,
+            Line::MaxNormals(n) => {
+                if self.normals.len() > n {
+                    return Err(ParseErrorKind::TooManyNormals(n));
+                }
+                self.max_normals = Some(n);
+                reserve(&mut self.normals, n);
+            }
+            Line::MaxVertices(n) => {
+                if self.vertices.len() > n {
+                    return Err(ParseErrorKind::TooManyVertices(n));
+                }
+                self.max_vertices = Some(n);
+                reserve(&mut self.vertices, n);
+            }
+            Line::Normal(x, y, z) => {
+                self.normals.push(Vector3::new(x, y, z));
+                if Some(self.normals.len() - 1) == self.max_normals {
+                    return Err(ParseErrorKind::TooManyNormals(self.normals.len() - 1));
+                }
+            }
+            Line::NormalTriangle(v1, v2, v3, n1, n2, n3) => {
+                self.add_triangle([v1, v2, v3], Some([n1, n2, n3]))?
+            }
+            Line::Output(path) => self.scene.output_image = Some(path),
+            Line::PointLight(r, g, b, x, y, z, i, attenuation) => {
+                self.scene.lights.push(DynamicLight::Point(Point {
+                    color: convert_color(r, g, b),
+                    position: Vector3::new(x, y, z),
+                    intensity: i,
+                    attenuation: attenuation.unwrap_or_default(),
+                }));
+            }
+            Line::Resolution(w, h) => {
+                self.scene.height = h;
+                self.scene.width = w;
+            }
+            Line::Plane(px, py, pz, nx, ny, nz) => {
+                self.scene.objects.push(DynamicRenderable::Plane(Plane {
+                    point: Vector3::new(px, py, pz),
+                    normal: Vector3::new(nx, ny, nz),
+                    material: self.material,
+                }));
+            }
+            Line::Sphere(x, y, z, r) => {
+                self.scene.objects.push(DynamicRenderable::Sphere(Sphere {
+                    position: Vector3::new(x, y, z),
+                    radius: r,
+                    material: self.material,
+                }));
+            }
+            Line::SpotLight(r, g, b, px, py, pz, dx, dy, dz, a1, a2, i, attenuation) => {
+                self.scene.lights.push(DynamicLight::Spot(Spot {
+                    color: convert_color(r, g, b),
+                    position: Vector3::new(px, py, pz),
+                    direction: Vector3::new(dx, dy, dz),
+                    intensity: i,
+                    falloff_angle: a1,
+                    max_angle: a2,
+                    attenuation: attenuation.unwrap_or_default(),
+                }));
+            }
+            Line::Triangle(v1, v2, v3) => self.add_triangle([v1, v2, v3], None)?,
+            Line::Vertex(x, y, z) => {
+                self.vertices.push(Vector3::new(x, y, z));
+                if Some(self.vertices.len() - 1) == self.max_vertices {
+                    return Err(ParseErrorKind::TooManyVertices(self.vertices.len() - 1));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a triangle from a `triangle` or `normal_triangle` directive to
+    /// the current run, given the indices of its vertices and normals. A
+    /// triangle with normals can't join a run without them, or the other way
+    /// around, so it starts a new one.
+    fn add_triangle(
+        &mut self,
+        vertices: [usize; 3],
+        normals: Option<[usize; 3]>,
+    ) -> Result<(), ParseErrorKind> {
+        if let Some(&v) = vertices.iter().find(|&&v| v >= self.vertices.len()) {
+            return Err(ParseErrorKind::NoSuchVertex(v));
+        }
+        if let Some(&n) = normals.iter().flatten().find(|&&n| n >= self.normals.len()) {
+            return Err(ParseErrorKind::NoSuchNormal(n));
+        }
+        if self
+            .run
+            .as_ref()
+            .is_some_and(|run| run.normals.is_some() != normals.is_some())
+        {
+            self.end_run();
+        }
+
+        let run = self.run.get_or_insert_with(|| TriangleRun {
+            normals: normals.map(|_| Vec::new()),
+            ..TriangleRun::default()
+        });
+        let mut face = [0; 3];
+        for (k, index) in face.iter_mut().enumerate() {
+            let (v, n) = (vertices[k], normals.map(|n| n[k]));
+            let next = run.positions.len() as u32;
+            *index = *run.indices.entry((v, n)).or_insert(next);
+            if *index == next {
+                run.positions.push(self.vertices[v]);
+                if let (Some(run_normals), Some(n)) = (run.normals.as_mut(), n) {
+                    run_normals.push(self.normals[n]);
+                }
+            }
+        }
+        run.faces.push(face);
+        Ok(())
+    }
+
+    /// Adds the current run of triangles to the scene as a mesh, made of the
+    /// current material.
+    fn end_run(&mut self) {
+        if let Some(run) = self.run.take() {
+            let material = self.material_index();
+            self.scene.meshes.push(Mesh {
+                positions: Arc::new(run.positions),
+                normals: run.normals.map(Arc::new),
+                colors: None,
+                faces: Arc::new(run.faces),
+                material,
+            });
+        }
+    }
+
+    /// Returns the scene, once every line has been applied.
+    pub fn finish(mut self) -> Scene {
+        self.end_run();
+        self.scene
+    }
+
+    /// Returns the index of the current material in the scene's `materials`,
+    /// adding it if it isn't there.
+    fn material_index(&mut self) -> usize {
+        let material = self.material;
+        match self.scene.materials.iter().position(|&m| m == material) {
+            Some(i) => i,
+            None => {
+                self.scene.materials.push(material);
+                self.scene.materials.len() - 1
+            }
+        }
+    }
+
+    /// Adds an instance of a mesh to the scene, made of the current material.
+    /// It shares the mesh's geometry, apart from the parts `mesh_transform`
+    /// changes, which it gets its own copy of.
+    pub fn add_mesh(&mut self, mesh: &Mesh) {
+        self.end_run();
+        let mut mesh = mesh.clone();
+        if let Some((offset, scale)) = self.mesh_transform {
+            for p in Arc::make_mut(&mut mesh.positions) {
+                *p = scale * *p + offset;
+            }
+            // Scaling by a negative factor mirrors the mesh, which flips its
+            // normals, but not the order of its vertices.
+            if scale < 0.0 {
+                for face in Arc::make_mut(&mut mesh.faces) {
+                    face.swap(1, 2);
+                }
+                if let Some(ref mut normals) = mesh.normals {
+                    for n in Arc::make_mut(normals) {
+                        *n = -*n;
+                    }
+                }
+            }
+        }
+        if !self.vertex_colors {
+            mesh.colors = None;
+        }
+
+        mesh.material = self.material_index();
+        self.scene.meshes.push(mesh);
+    }
+}
+
+/// The most elements to reserve space for because of a `max_vertices` or
+/// `max_normals` directive, so that a huge maximum can't exhaust memory before
+/// any vertices are read.
+const MAX_RESERVE: usize = 1 << 24;
+
+/// Reserves space for a vector to hold `n` elements in total.
+fn reserve<T>(vec: &mut Vec<T>, n: usize) {
+    let additional = n.min(MAX_RESERVE).saturating_sub(vec.len());
+    // If this fails, the vector will just grow as usual.
+    let _ = vec.try_reserve_exact(additional);
+}
+
+fn convert_color(r: f32, g: f32, b: f32) -> Rgb<f32> {
+    Rgb {
+        data: [clamp(r, 0.0, 1.0), clamp(g, 0.0, 1.0), clamp(b, 0.0, 1.0)],
+    }
+}
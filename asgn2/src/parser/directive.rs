@@ -0,0 +1,402 @@
+use std::io::{BufRead, Result as IoResult};
+use std::path::PathBuf;
+
+use light::Attenuation;
+use parser::error::lookup_directive;
+use parser::{ArgKind, Line, ParseError, ParseErrorKind};
+
+/// The position of a token within one of a directive's lines, as a byte range.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Token {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
+/// An error in a directive, at one of its tokens or at the whole directive.
+pub type DirectiveError = (Option<Token>, ParseErrorKind);
+
+/// A directive's name and arguments, as read from the input. The arguments
+/// may continue onto following lines, up to the next line that starts with a
+/// letter, or the name of another directive.
+#[derive(Debug)]
+pub struct Directive {
+    /// The line number of the first line of the directive, starting from 1.
+    first_line: usize,
+
+    /// The lines the directive spans, with line endings removed. They may
+    /// hold other directives too, when several share a line.
+    lines: Vec<String>,
+
+    /// The tokens of the directive. The first is its name.
+    tokens: Vec<Token>,
+}
+
+impl Directive {
+    /// Returns the token for the name of the directive.
+    pub fn name_token(&self) -> Token {
+        self.tokens[0]
+    }
+
+    /// Returns the name of the directive.
+    pub fn name(&self) -> &str {
+        self.text(self.tokens[0])
+    }
+
+    /// Returns the tokens for the arguments of the directive.
+    pub fn args(&self) -> &[Token] {
+        &self.tokens[1..]
+    }
+
+    /// Returns the text of one of the directive's tokens.
+    pub fn text(&self, token: Token) -> &str {
+        &self.lines[token.line][token.start..token.end]
+    }
+
+    /// Returns whether the directive takes a path, which is the rest of the
+    /// line after its name, `#`s and all.
+    pub fn takes_path(&self) -> bool {
+        takes_path(self.name())
+    }
+
+    /// Returns the argument of a directive that takes a path.
+    pub fn path_arg(&self) -> Result<&str, DirectiveError> {
+        match *self.args() {
+            [] => Err((None, ParseErrorKind::TooFewArguments(0))),
+            [path] => Ok(self.text(path)),
+            [_, extra, ..] => Err((Some(extra), ParseErrorKind::TooManyArguments)),
+        }
+    }
+
+    /// Creates an error in the directive, at the given token, or spanning the
+    /// directive's first line if no token is given.
+    pub fn error(&self, token: Option<Token>, kind: ParseErrorKind) -> ParseError {
+        let token = token.unwrap_or_else(|| {
+            let name = self.tokens[0];
+            let last = self.tokens.iter().rev().find(|t| t.line == name.line);
+            Token {
+                end: last.map_or(name.end, |t| t.end),
+                ..name
+            }
+        });
+        ParseError::at(
+            self.first_line + token.line,
+            &self.lines[token.line],
+            token.start,
+            token.end - token.start,
+            Some(self.name()),
+            kind,
+        )
+    }
+
+    /// Returns whether a word on the line with the given index, which is
+    /// about to be added, starts the next directive.
+    fn starts_next(&self, line: &str, line_index: usize, start: usize, end: usize) -> bool {
+        let word = &line[start..end];
+        match self.tokens[..] {
+            [] => false,
+            [name] if name.line == line_index && &line[name.start..name.end] == "define" => false,
+            [.., last] if last.line != line_index && word.starts_with(char::is_alphabetic) => true,
+            _ => word == "define" || lookup_directive(word).is_some(),
+        }
+    }
+
+    /// Adds a line to the directive, reading its tokens from the byte offset
+    /// `from` on. Returns the offset the next directive starts at, if it
+    /// starts on this line.
+    ///
+    /// The next directive starts at a line starting with a letter, or at the
+    /// name of a directive, other than the name a `define` directive defines.
+    /// A word starting with `#` starts a comment, other than in a path.
+    fn push_line(&mut self, line: String, from: usize) -> Option<usize> {
+        let line_index = self.lines.len();
+        let mut next = None;
+        let mut pos = from;
+        while let Some(start) = line[pos..]
+            .find(|c: char| !c.is_whitespace())
+            .map(|i| pos + i)
+        {
+            let word = &line[start..];
+            if word.starts_with('#') {
+                break;
+            }
+            let end = word
+                .find(char::is_whitespace)
+                .map_or(line.len(), |i| start + i);
+            if self.starts_next(&line, line_index, start, end) {
+                next = Some(start);
+                break;
+            }
+            self.tokens.push(Token {
+                line: line_index,
+                start,
+                end,
+            });
+            pos = end;
+
+            if self.tokens.len() == 1 && takes_path(&line[start..end]) {
+                let rest = &line[end..];
+                let path = rest.trim();
+                if !path.is_empty() {
+                    let start = end + rest.len() - rest.trim_start().len();
+                    self.tokens.push(Token {
+                        line: line_index,
+                        start,
+                        end: start + path.len(),
+                    });
+                }
+                break;
+            }
+        }
+        self.lines.push(line);
+        next
+    }
+}
+
+/// Returns whether the directive with the given name takes a path.
+fn takes_path(name: &str) -> bool {
+    lookup_directive(name).is_some_and(|(_, kind, _)| kind == ArgKind::Path)
+}
+
+/// Reads directives from the input one at a time, so that the whole input
+/// needn't be held in memory.
+pub struct Directives<R> {
+    reader: R,
+
+    /// The number of lines read so far.
+    line: usize,
+
+    /// The line the next directive starts on, if it's already been read,
+    /// with its line number and the offset the directive starts at.
+    next_line: Option<(usize, String, usize)>,
+}
+
+impl<R: BufRead> Directives<R> {
+    /// Creates a reader for directives from the given input.
+    pub fn new(reader: R) -> Directives<R> {
+        Directives {
+            reader,
+            line: 0,
+            next_line: None,
+        }
+    }
+
+    /// Reads the next directive, returning `None` at the end of the input.
+    pub fn next(&mut self) -> IoResult<Option<Directive>> {
+        let mut directive = Directive {
+            first_line: 0,
+            lines: Vec::new(),
+            tokens: Vec::new(),
+        };
+        let mut next = None;
+        while directive.tokens.is_empty() {
+            let (number, line, from) = match self.next_line.take() {
+                Some(next_line) => next_line,
+                None => match self.read_line()? {
+                    Some(line) => (self.line, line, 0),
+                    None => return Ok(None),
+                },
+            };
+            directive.first_line = number;
+            directive.lines.clear();
+            next = directive.push_line(line, from);
+        }
+
+        while next.is_none() {
+            match self.read_line()? {
+                Some(line) => next = directive.push_line(line, 0),
+                None => break,
+            }
+        }
+        if let Some(from) = next {
+            // The line stays part of this directive if any of its tokens are
+            // on it.
+            let index = directive.lines.len() - 1;
+            let line = if directive.tokens.last().is_some_and(|t| t.line == index) {
+                directive.lines[index].clone()
+            } else {
+                directive.lines.pop().unwrap_or_default()
+            };
+            self.next_line = Some((directive.first_line + index, line, from));
+        }
+        Ok(Some(directive))
+    }
+
+    /// Reads a line, without its line ending.
+    fn read_line(&mut self) -> IoResult<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        if line.ends_with('\r') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+}
+
+impl Line {
+    /// Parses a line from a directive, given the directive's arguments after
+    /// variables have been substituted, and the tokens they came from.
+    pub(crate) fn parse(
+        directive: &Directive,
+        args: &[(&str, Token)],
+    ) -> Result<Line, DirectiveError> {
+        let unknown = (
+            Some(directive.name_token()),
+            ParseErrorKind::UnknownDirective,
+        );
+        let (name, kind, arity) =
+            lookup_directive(directive.name()).ok_or_else(|| unknown.clone())?;
+        if kind == ArgKind::Path {
+            let path = directive.path_arg()?;
+            return match name {
+                "output_image" => Ok(Line::Output(PathBuf::from(path))),
+                _ => Err(unknown),
+            };
+        }
+
+        let mut f = Vec::new();
+        let mut n = Vec::new();
+        for (i, &(arg, token)) in args.iter().enumerate() {
+            if i >= arity.max {
+                return Err((Some(token), ParseErrorKind::TooManyArguments));
+            }
+            let bad = (Some(token), ParseErrorKind::BadArgument(kind));
+            match kind {
+                ArgKind::Float => f.push(parse_float(arg).ok_or(bad)?),
+                _ => n.push(parse_integer(arg).ok_or(bad)?),
+            }
+        }
+        if args.len() < arity.min {
+            return Err((None, ParseErrorKind::TooFewArguments(args.len())));
+        }
+
+        let intensity = |i: usize| f.get(i).cloned().unwrap_or(1.0);
+        // Attenuation coefficients that aren't given are zero, unless none
+        // are, in which case the light has the default attenuation.
+        let attenuation = |i: usize| {
+            let coefficient = |i: usize| f.get(i).cloned().unwrap_or(0.0);
+            if f.len() > i {
+                Some(Attenuation {
+                    constant: coefficient(i),
+                    linear: coefficient(i + 1),
+                    quadratic: coefficient(i + 2),
+                })
+            } else {
+                None
+            }
+        };
+        Ok(match name {
+            "ambient_light" => Line::AmbientLight(f[0], f[1], f[2]),
+            "attenuation_distance" => Line::AttenuationDistance(f[0]),
+            "back_material" => {
+                Line::BackMaterial(f[0], f[1], f[2], f[3], f[4], f[5], f[6], f[7], f[8], f[9])
+            }
+            "background" => Line::Background(f[0], f[1], f[2]),
+            "camera" => Line::Camera(f[0], f[1], f[2], f[3], f[4], f[5], f[6], f[7], f[8], f[9]),
+            "directional_light" => {
+                Line::DirectionalLight(f[0], f[1], f[2], f[3], f[4], f[5], intensity(6))
+            }
+            "emissive" => Line::Emissive(f[0], f[1], f[2], intensity(3)),
+            "film_resolution" => {
+                let dim = |i: usize| match n[i] {
+                    x if x <= u32::MAX as usize => Ok(x as u32),
+                    _ => Err((Some(args[i].1), ParseErrorKind::BadArgument(kind))),
+                };
+                Line::Resolution(dim(0)?, dim(1)?)
+            }
+            "light_samples" => Line::LightSamples(n[0]),
+            "material" => Line::Material(
+                f[0], f[1], f[2], f[3], f[4], f[5], f[6], f[7], f[8], f[9], f[10], f[11], f[12],
+                f[13],
+            ),
+            "max_depth" => Line::MaxDepth(n[0]),
+            "mesh_transform" => Line::MeshTransform(f[0], f[1], f[2], f[3]),
+            "max_normals" => Line::MaxNormals(n[0]),
+            "max_vertices" => Line::MaxVertices(n[0]),
+            "normal" => Line::Normal(f[0], f[1], f[2]),
+            "normal_triangle" => Line::NormalTriangle(n[0], n[1], n[2], n[3], n[4], n[5]),
+            "plane" => Line::Plane(f[0], f[1], f[2], f[3], f[4], f[5]),
+            "point_light" => Line::PointLight(
+                f[0],
+                f[1],
+                f[2],
+                f[3],
+                f[4],
+                f[5],
+                intensity(6),
+                attenuation(7),
+            ),
+            "samples" => Line::Samples(n[0]),
+            "single_sided" => Line::SingleSided,
+            "sphere" => Line::Sphere(f[0], f[1], f[2], f[3]),
+            "spot_light" => Line::SpotLight(
+                f[0],
+                f[1],
+                f[2],
+                f[3],
+                f[4],
+                f[5],
+                f[6],
+                f[7],
+                f[8],
+                f[9],
+                f[10],
+                intensity(11),
+                attenuation(12),
+            ),
+            "transparent_background" => Line::TransparentBackground,
+            "triangle" => Line::Triangle(n[0], n[1], n[2]),
+            "vertex" => Line::Vertex(f[0], f[1], f[2]),
+            "vertex_colors" => Line::VertexColors,
+            _ => return Err(unknown),
+        })
+    }
+}
+
+/// Parses a number, which has an optional sign, digits with an optional
+/// decimal point, and an optional exponent.
+fn parse_float(s: &str) -> Option<f32> {
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let rest = s.trim_start_matches(&['+', '-'][..]);
+    if s.len() - rest.len() > 1 {
+        return None;
+    }
+    let int = digits(rest);
+    let mut rest = &rest[int..];
+    let mut frac = 0;
+    if rest.starts_with('.') {
+        frac = digits(&rest[1..]);
+        rest = &rest[1 + frac..];
+    }
+    if int == 0 && frac == 0 {
+        return None;
+    }
+    if rest.starts_with(&['e', 'E'][..]) {
+        let exp = rest[1..].trim_start_matches('-');
+        if rest.len() - exp.len() > 2 || digits(exp) == 0 {
+            return None;
+        }
+        rest = &exp[digits(exp)..];
+    }
+    if rest.is_empty() {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parses a non-negative integer, which is a sequence of digits.
+fn parse_integer(s: &str) -> Option<usize> {
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
@@ -0,0 +1,345 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+
+/// The kind of value a directive's argument must be.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArgKind {
+    /// A floating-point number.
+    Float,
+
+    /// A non-negative integer.
+    Integer,
+
+    /// A path, which takes up the rest of the line.
+    Path,
+
+    /// The name of a variable.
+    Name,
+}
+
+/// The number of arguments a directive takes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Arity {
+    /// The minimum number of arguments.
+    pub min: usize,
+
+    /// The maximum number of arguments.
+    pub max: usize,
+}
+
+impl Display for Arity {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let plural = if self.max == 1 { "" } else { "s" };
+        if self.min == self.max {
+            write!(fmt, "{} argument{}", self.max, plural)
+        } else if self.max > self.min + 1 {
+            write!(fmt, "{} to {} arguments", self.min, self.max)
+        } else {
+            write!(fmt, "{} or {} argument{}", self.min, self.max, plural)
+        }
+    }
+}
+
+/// Every directive the parser knows about, with the kind of its arguments and
+/// the minimum and maximum number of them.
+const DIRECTIVES: &[(&str, ArgKind, usize, usize)] = &[
+    ("ambient_light", ArgKind::Float, 3, 3),
+    ("attenuation_distance", ArgKind::Float, 1, 1),
+    ("back_material", ArgKind::Float, 10, 10),
+    ("background", ArgKind::Float, 3, 3),
+    ("camera", ArgKind::Float, 10, 10),
+    ("directional_light", ArgKind::Float, 6, 7),
+    ("emissive", ArgKind::Float, 3, 4),
+    ("film_resolution", ArgKind::Integer, 2, 2),
+    ("include", ArgKind::Path, 1, 1),
+    ("light_samples", ArgKind::Integer, 1, 1),
+    ("material", ArgKind::Float, 14, 14),
+    ("max_depth", ArgKind::Integer, 1, 1),
+    ("max_normals", ArgKind::Integer, 1, 1),
+    ("max_vertices", ArgKind::Integer, 1, 1),
+    ("mesh", ArgKind::Path, 1, 1),
+    ("mesh_transform", ArgKind::Float, 4, 4),
+    ("normal", ArgKind::Float, 3, 3),
+    ("normal_triangle", ArgKind::Integer, 6, 6),
+    ("output_image", ArgKind::Path, 1, 1),
+    ("plane", ArgKind::Float, 6, 6),
+    ("point_light", ArgKind::Float, 6, 10),
+    ("samples", ArgKind::Integer, 1, 1),
+    ("single_sided", ArgKind::Float, 0, 0),
+    ("sphere", ArgKind::Float, 4, 4),
+    ("spot_light", ArgKind::Float, 11, 15),
+    ("transparent_background", ArgKind::Float, 0, 0),
+    ("triangle", ArgKind::Integer, 3, 3),
+    ("vertex", ArgKind::Float, 3, 3),
+    ("vertex_colors", ArgKind::Float, 0, 0),
+];
+
+/// Looks up the argument kind and arity of a directive.
+pub(crate) fn lookup_directive(name: &str) -> Option<(&'static str, ArgKind, Arity)> {
+    DIRECTIVES
+        .iter()
+        .find(|&&(n, _, _, _)| n == name)
+        .map(|&(n, kind, min, max)| (n, kind, Arity { min, max }))
+}
+
+/// What went wrong while parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    /// The directive isn't one the parser knows about.
+    UnknownDirective,
+
+    /// The directive was given fewer arguments than it requires.
+    TooFewArguments(usize),
+
+    /// The directive was given more arguments than it accepts.
+    TooManyArguments,
+
+    /// An argument was not of the kind the directive requires.
+    BadArgument(ArgKind),
+
+    /// The directive couldn't be parsed for some other reason.
+    Invalid,
+
+    /// A non-existent normal was referenced.
+    NoSuchNormal(usize),
+
+    /// A non-existent vertex was referenced.
+    NoSuchVertex(usize),
+
+    /// More normals were defined than the given maximum, which was set by
+    /// `max_normals`.
+    TooManyNormals(usize),
+
+    /// More vertices were defined than the given maximum, which was set by
+    /// `max_vertices`.
+    TooManyVertices(usize),
+
+    /// A variable was used without being defined.
+    UndefinedVariable(String),
+
+    /// A file included itself, directly or indirectly.
+    IncludeCycle(PathBuf),
+
+    /// A file couldn't be read.
+    Io(PathBuf, String),
+
+    /// A structured scene description was invalid, for the given reason.
+    Structured(String),
+}
+
+/// An error while parsing, along with where in the input it occurred.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// What went wrong.
+    pub kind: ParseErrorKind,
+
+    /// The file the error occurred in, if the input came from a file.
+    pub file: Option<PathBuf>,
+
+    /// The line the error occurred on, starting from 1, or 0 if the error
+    /// isn't tied to a line.
+    pub line: usize,
+
+    /// The column the error occurred at, in characters, starting from 1.
+    pub column: usize,
+
+    /// The length of the erroneous text, in characters.
+    pub len: usize,
+
+    /// The name of the directive the error occurred in, if known.
+    pub directive: Option<String>,
+
+    /// The number of arguments the directive takes, if known.
+    pub arity: Option<Arity>,
+
+    /// The text of the line the error occurred on.
+    pub source_line: String,
+}
+
+impl ParseError {
+    /// Creates an error for the text of the given length at the given byte
+    /// offset into the input.
+    pub(crate) fn new(
+        input: &str,
+        offset: usize,
+        len: usize,
+        directive: Option<&str>,
+        kind: ParseErrorKind,
+    ) -> ParseError {
+        let line_start = input[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = input[offset..]
+            .find('\n')
+            .map(|i| offset + i)
+            .unwrap_or(input.len());
+        ParseError::at(
+            input[..offset].matches('\n').count() + 1,
+            &input[line_start..line_end],
+            offset - line_start,
+            len.min(line_end - offset),
+            directive,
+            kind,
+        )
+    }
+
+    /// Creates an error for the text at the given byte range of a line.
+    pub(crate) fn at(
+        line: usize,
+        source_line: &str,
+        start: usize,
+        len: usize,
+        directive: Option<&str>,
+        kind: ParseErrorKind,
+    ) -> ParseError {
+        ParseError {
+            kind,
+            file: None,
+            line,
+            column: source_line[..start].chars().count() + 1,
+            len: source_line[start..start + len].chars().count(),
+            directive: directive.map(|d| d.to_string()),
+            arity: directive.and_then(lookup_directive).map(|(_, _, a)| a),
+            source_line: source_line.trim_end().to_string(),
+        }
+    }
+
+    /// Creates an error for a file that couldn't be read at all.
+    pub(crate) fn io(path: &Path, err: IoError) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::Io(path.to_path_buf(), err.to_string()),
+            file: Some(path.to_path_buf()),
+            line: 0,
+            column: 0,
+            len: 0,
+            directive: None,
+            arity: None,
+            source_line: String::new(),
+        }
+    }
+
+    /// Returns the error message without the source line, for where the
+    /// source shouldn't be shown.
+    pub fn message(&self) -> String {
+        struct Message<'a>(&'a ParseError);
+        impl<'a> Display for Message<'a> {
+            fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+                self.0.fmt_message(fmt)
+            }
+        }
+        Message(self).to_string()
+    }
+
+    /// Writes the error message, without the source line.
+    fn fmt_message(&self, fmt: &mut Formatter) -> FmtResult {
+        let directive = self.directive.as_ref().map_or("", |s| &**s);
+        if let Some(ref file) = self.file {
+            write!(fmt, "{}: ", file.display())?;
+        }
+        if self.line != 0 {
+            write!(fmt, "line {}, column {}: ", self.line, self.column)?;
+        }
+        match self.kind {
+            ParseErrorKind::UnknownDirective => write!(fmt, "unknown directive `{}`", directive)?,
+            ParseErrorKind::TooFewArguments(found) => match self.arity {
+                Some(arity) => write!(
+                    fmt,
+                    "`{}` takes {}, but {} were given",
+                    directive, arity, found
+                )?,
+                None => write!(fmt, "`{}` needs more arguments", directive)?,
+            },
+            ParseErrorKind::TooManyArguments => match self.arity {
+                Some(arity) => write!(fmt, "unexpected argument; `{}` takes {}", directive, arity)?,
+                None => write!(fmt, "unexpected argument to `{}`", directive)?,
+            },
+            ParseErrorKind::BadArgument(kind) => {
+                let expected = match kind {
+                    ArgKind::Float => "a number",
+                    ArgKind::Integer => "a non-negative integer",
+                    ArgKind::Path => "a path",
+                    ArgKind::Name => "a variable name",
+                };
+                write!(fmt, "`{}` expected {} as an argument", directive, expected)?;
+            }
+            ParseErrorKind::Invalid => write!(fmt, "invalid `{}` directive", directive)?,
+            ParseErrorKind::NoSuchNormal(n) => write!(fmt, "no normal with index {}", n)?,
+            ParseErrorKind::NoSuchVertex(n) => write!(fmt, "no vertex with index {}", n)?,
+            ParseErrorKind::TooManyNormals(n) => {
+                write!(fmt, "more normals than the `max_normals` of {}", n)?
+            }
+            ParseErrorKind::TooManyVertices(n) => {
+                write!(fmt, "more vertices than the `max_vertices` of {}", n)?
+            }
+            ParseErrorKind::UndefinedVariable(ref name) => {
+                write!(fmt, "undefined variable `{}`", name)?
+            }
+            ParseErrorKind::IncludeCycle(ref path) => {
+                write!(fmt, "{} includes itself", path.display())?
+            }
+            ParseErrorKind::Io(ref path, ref err) => {
+                write!(fmt, "couldn't read {}: {}", path.display(), err)?
+            }
+            ParseErrorKind::Structured(ref msg) => write!(fmt, "{}", msg)?,
+        }
+        Ok(())
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        self.fmt_message(fmt)?;
+        if self.line == 0 {
+            return Ok(());
+        }
+
+        // Print the line with the erroneous part underlined. Tabs are kept in
+        // the underline's indentation so it lines up with the source line.
+        let gutter = self.line.to_string().len();
+        let indent = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        writeln!(fmt)?;
+        writeln!(fmt, "{:2$} | {}", self.line, self.source_line, gutter)?;
+        write!(
+            fmt,
+            "{:3$} | {}{}",
+            "",
+            indent,
+            "^".repeat(self.len.max(1)),
+            gutter
+        )
+    }
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        "error parsing scene"
+    }
+}
+
+/// All the errors found while parsing a scene.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseErrors(pub Vec<ParseError>);
+
+impl Display for ParseErrors {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        for (i, err) in self.0.iter().enumerate() {
+            if i != 0 {
+                writeln!(fmt)?;
+                writeln!(fmt)?;
+            }
+            write!(fmt, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseErrors {
+    fn description(&self) -> &str {
+        "errors parsing scene"
+    }
+}
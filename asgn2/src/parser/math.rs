@@ -10,9 +10,11 @@ pub fn normal_from_normals(
     _v1: Vector3<f32>,
     _v2: Vector3<f32>,
     _v3: Vector3<f32>,
-    _n1: Vector3<f32>,
-    _n2: Vector3<f32>,
-    _n3: Vector3<f32>,
+    n1: Vector3<f32>,
+    n2: Vector3<f32>,
+    n3: Vector3<f32>,
 ) -> Vector3<f32> {
-    unimplemented!()
+    // Triangles only have a single normal, so use the average of the vertex
+    // normals.
+    n1 + n2 + n3
 }
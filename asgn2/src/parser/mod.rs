@@ -1,178 +1,312 @@
 //! A parser for the file format used for scenes.
+//!
+//! A scene file is a sequence of directives, each a name followed by
+//! whitespace-separated arguments. A directive's arguments may continue onto
+//! following lines, up to the next line that starts with a letter, and
+//! several directives may share a line, each starting at its name. A word
+//! starting with `#` starts a comment, which runs to the end of the line. The paths `include`, `mesh` and `output_image` take are the rest
+//! of the line, though, including any `#`s. The input is parsed as it's read,
+//! so large scenes needn't be held in memory.
+//!
+//! Besides the directives describing the scene, three directives are handled
+//! by the parser itself:
+//!
+//! - `include PATH` parses another scene file as if it appeared in place of
+//!   the directive. Relative paths are relative to the including file, or to
+//!   the current directory when parsing a string.
+//! - `mesh PATH` adds a PLY or STL file to the scene's `meshes`, made of the
+//!   current material, with paths resolved as for `include`. Each file
+//!   is only read once, however many times it's used, and the meshes made
+//!   from it share its vertices and faces where they can. A preceding
+//!   `mesh_transform x y z s` directive scales the meshes after it by `s` and
+//!   moves them by `(x, y, z)`, and a `vertex_colors` directive after a
+//!   `material` one uses the colors of the meshes' vertices, if they have
+//!   them, as their triangles' diffuse colors. See `Mesh`.
+//! - `define NAME ARGS...` defines a variable; `$NAME` can then be used in
+//!   place of arguments to later directives, and is replaced with `ARGS`. For
+//!   example, `define red 1 0 0` allows `ambient_light $red`, and a whole
+//!   material can be defined and used as `material $glass`.
+//!
+//! A `material` directive sets the material of the objects after it, and
+//! these directives after it change parts of that material:
+//!
+//! - `emissive r g b [i]` makes it give off light of the given color and
+//!   intensity, which defaults to 1.
+//! - `attenuation_distance D` tints the shadows of transmissive objects by
+//!   their thickness, following the Beer-Lambert law: light is scaled by the
+//!   transmissive color raised to the power of the distance it travels inside
+//!   the object over `D`. This only makes sense for closed objects.
+//! - `single_sided` makes only the front of surfaces, which their normals
+//!   point out of, able to be hit.
+//! - `back_material ar ag ab dr dg db sr sg sb ns` gives the back of surfaces
+//!   a different appearance, with the same arguments as the first ten of
+//!   `material`.
+//!
+//! Scenes can also be described in JSON, TOML, or YAML; see `Format`.
 
-mod math;
-mod nom;
+mod builder;
+mod directive;
+mod error;
+pub(crate) mod math;
+mod structured;
 #[cfg(test)]
 mod tests;
+mod writer;
 
-use std::path::PathBuf;
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Result as IoResult};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use cgmath::num_traits::clamp;
-use cgmath::{InnerSpace, Vector3};
-use image::Rgb;
-use nom::{Err, IResult};
-
-use light::{Directional, DynamicLight, Point, Spot};
-use material::Material;
-use renderable::{DynamicRenderable, Plane, Sphere, Triangle};
+use light::Attenuation;
+use mesh::Mesh;
+use parser::builder::SceneBuilder;
+use parser::directive::{Directive, DirectiveError, Directives, Token};
 use scene::Scene;
 
+pub use parser::error::{ArgKind, Arity, ParseError, ParseErrorKind, ParseErrors};
+pub use parser::structured::Format;
+pub(crate) use parser::structured::{deserialize, serialize};
+
 impl FromStr for Scene {
-    type Err = ParseError;
-    fn from_str(input: &str) -> Result<Scene, ParseError> {
-        let lines = match nom::parse_lines(input) {
-            IResult::Done("", lines) => Ok(lines),
-            IResult::Done(rest, _) => Err(ParseError::Unparsed(rest.to_string())),
-            IResult::Incomplete(_) => unreachable!(),
-            IResult::Error(e) => Err(e.into()),
-        }?;
-
-        let mut scene = Scene::default();
-        let mut vertices = Vec::new();
-        let mut normals = Vec::new();
-        let mut material = Material::default();
-        for line in lines {
-            match line {
-                Line::AmbientLight(r, g, b) => scene.ambient_light = convert_color(r, g, b),
-                Line::Background(r, g, b) => scene.background = convert_color(r, g, b),
-                Line::Camera(px, py, pz, dx, dy, dz, ux, uy, uz, ha) => {
-                    scene.camera_position = Vector3::new(px, py, pz);
-                    scene.camera_direction = Vector3::new(dx, dy, dz).normalize();
-                    scene.camera_up = Vector3::new(ux, uy, uz).normalize();
-                    scene.camera_half_angle_tan = ha.to_radians().tan();
-                }
-                Line::DirectionalLight(r, g, b, x, y, z, i) => {
-                    scene.lights.push(DynamicLight::Directional(Directional {
-                        color: convert_color(r, g, b),
-                        direction: Vector3::new(x, y, z),
-                        intensity: i,
-                    }));
-                }
-                Line::Material(ar, ag, ab, dr, dg, db, sr, sg, sb, ns, tr, tg, tb, ior) => {
-                    material = Material {
-                        ambient: convert_color(ar, ag, ab),
-                        diffuse: convert_color(dr, dg, db),
-                        specular: convert_color(sr, sg, sb),
-                        phong: ns,
-                        transmissive: convert_color(tr, tg, tb),
-                        ior,
-                    };
-                }
-                Line::MaxDepth(n) => scene.max_collisions = n,
-                Line::MaxNormals(_) | Line::MaxVertices(_) => {
-                    warn!("Ignoring {:?}", line);
-                }
-                Line::Normal(x, y, z) => normals.push(Vector3::new(x, y, z)),
-                Line::NormalTriangle(v1, v2, v3, n1, n2, n3) => {
-                    // TODO: This could *greatly* benefit from macros.
-                    let v1 = if let Some(&v) = vertices.get(v1) {
-                        v
-                    } else {
-                        return Err(ParseError::NoSuchVertex(line, v1));
-                    };
-                    let v2 = if let Some(&v) = vertices.get(v2) {
-                        v
-                    } else {
-                        return Err(ParseError::NoSuchVertex(line, v2));
-                    };
-                    let v3 = if let Some(&v) = vertices.get(v3) {
-                        v
-                    } else {
-                        return Err(ParseError::NoSuchVertex(line, v3));
-                    };
-                    let n1 = if let Some(&n) = normals.get(n1) {
-                        n
-                    } else {
-                        return Err(ParseError::NoSuchNormal(line, n1));
-                    };
-                    let n2 = if let Some(&n) = normals.get(n2) {
-                        n
-                    } else {
-                        return Err(ParseError::NoSuchNormal(line, n2));
-                    };
-                    let n3 = if let Some(&n) = normals.get(n3) {
-                        n
-                    } else {
-                        return Err(ParseError::NoSuchNormal(line, n3));
-                    };
-                    let tri = Triangle {
-                        vertices: (v1, v2, v3),
-                        normal: math::normal_from_normals(v1, v2, v3, n1, n2, n3),
-                        material: material.clone(),
-                    };
-                    scene.objects.push(DynamicRenderable::Triangle(tri));
-                }
-                Line::Output(path) => scene.output_image = Some(path),
-                Line::PointLight(r, g, b, x, y, z, i) => {
-                    scene.lights.push(DynamicLight::Point(Point {
-                        color: convert_color(r, g, b),
-                        position: Vector3::new(x, y, z),
-                        intensity: i,
-                    }));
-                }
-                Line::Resolution(w, h) => {
-                    scene.height = h;
-                    scene.width = w;
-                }
-                Line::Plane(px, py, pz, nx, ny, nz) => {
-                    scene.objects.push(DynamicRenderable::Plane(Plane {
-                        point: Vector3::new(px, py, pz),
-                        normal: Vector3::new(nx, ny, nz),
-                        material: material.clone(),
-                    }));
-                }
-                Line::Sphere(x, y, z, r) => {
-                    scene.objects.push(DynamicRenderable::Sphere(Sphere {
-                        position: Vector3::new(x, y, z),
-                        radius: r,
-                        material: material.clone(),
-                    }));
+    type Err = ParseErrors;
+    fn from_str(input: &str) -> Result<Scene, ParseErrors> {
+        Scene::from_reader(input.as_bytes())
+    }
+}
+
+impl Scene {
+    /// Parses a scene from a file, resolving `include` directives relative to
+    /// it. Files with the extension of a structured `Format` are parsed as
+    /// that format instead.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Scene, ParseErrors> {
+        let path = path.as_ref();
+        let io_error = |err| ParseErrors(vec![ParseError::io(path, err)]);
+        if let Some(format) = Format::from_path(path) {
+            return read_structured(path, format, Scene::from_structured);
+        }
+
+        let file = File::open(path).map_err(io_error)?;
+        let mut parser = Parser::default();
+        parser.include_stack.push(canonical(path));
+        parser.parse(BufReader::new(file), Some(path));
+        parser.finish()
+    }
+
+    /// Parses a scene from a reader, in the scene file format.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Scene, ParseErrors> {
+        let mut parser = Parser::default();
+        parser.parse(reader, None);
+        parser.finish()
+    }
+}
+
+/// The state of the parser, which is shared between included files.
+#[derive(Debug, Default)]
+struct Parser {
+    /// The scene being built.
+    builder: SceneBuilder,
+
+    /// The variables defined so far, and the arguments they expand to.
+    defines: HashMap<String, Vec<String>>,
+
+    /// The files currently being parsed, used to detect include cycles.
+    include_stack: Vec<PathBuf>,
+
+    /// The meshes loaded so far, by their canonical paths.
+    meshes: HashMap<PathBuf, Mesh>,
+
+    /// The errors found so far.
+    errors: Vec<ParseError>,
+}
+
+impl Parser {
+    /// Parses the input, which came from the file at `path`, if any. Parsing
+    /// continues past errors, so that every error in the input is found at
+    /// once.
+    fn parse<R: BufRead>(&mut self, reader: R, path: Option<&Path>) {
+        let mut directives = Directives::new(reader);
+        loop {
+            match directives.next() {
+                Ok(Some(directive)) => match directive.name() {
+                    "include" => self.include(&directive, path),
+                    "mesh" => self.mesh(&directive, path),
+                    "define" => self.define(&directive, path),
+                    _ => self.line(&directive, path),
+                },
+                Ok(None) => break,
+                Err(err) => {
+                    let path = path.unwrap_or_else(|| Path::new("<input>"));
+                    self.error(ParseError::io(path, err), None);
+                    break;
                 }
-                Line::SpotLight(r, g, b, px, py, pz, dx, dy, dz, a1, a2, i) => {
-                    scene.lights.push(DynamicLight::Spot(Spot {
-                        color: convert_color(r, g, b),
-                        position: Vector3::new(px, py, pz),
-                        direction: Vector3::new(dx, dy, dz),
-                        intensity: i,
-                        falloff_angle: a1,
-                        max_angle: a2,
-                    }));
+            }
+        }
+    }
+
+    /// Parses a directive describing the scene, and applies it to the scene.
+    fn line(&mut self, directive: &Directive, path: Option<&Path>) {
+        let result =
+            substitute(&self.defines, directive).and_then(|args| Line::parse(directive, &args));
+        let err = match result {
+            Ok(line) => match self.builder.apply(line) {
+                Ok(()) => return,
+                Err(kind) => directive.error(None, kind),
+            },
+            Err((token, kind)) => directive.error(token, kind),
+        };
+        self.error(err, path);
+    }
+
+    /// Handles an `include` directive.
+    fn include(&mut self, directive: &Directive, path: Option<&Path>) {
+        let included = match resolve(directive, path) {
+            Ok(included) => included,
+            Err((token, kind)) => return self.error(directive.error(token, kind), path),
+        };
+        let canonical_path = canonical(&included);
+        if self.include_stack.contains(&canonical_path) {
+            let kind = ParseErrorKind::IncludeCycle(included);
+            return self.error(directive.error(None, kind), path);
+        }
+        let file = match File::open(&included) {
+            Ok(file) => file,
+            Err(err) => {
+                let kind = ParseErrorKind::Io(included, err.to_string());
+                return self.error(directive.error(None, kind), path);
+            }
+        };
+
+        self.include_stack.push(canonical_path);
+        self.parse(BufReader::new(file), Some(&included));
+        self.include_stack.pop();
+    }
+
+    /// Handles a `mesh` directive.
+    fn mesh(&mut self, directive: &Directive, path: Option<&Path>) {
+        let mesh_path = match resolve(directive, path) {
+            Ok(mesh_path) => mesh_path,
+            Err((token, kind)) => return self.error(directive.error(token, kind), path),
+        };
+        let mesh = match self.meshes.entry(canonical(&mesh_path)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match Mesh::from_file(&mesh_path) {
+                Ok(mesh) => entry.insert(mesh),
+                Err(err) => {
+                    let kind = ParseErrorKind::Io(mesh_path, err.to_string());
+                    return self.error(directive.error(None, kind), path);
                 }
-                Line::Triangle(v1, v2, v3) => {
-                    let v1 = if let Some(&v) = vertices.get(v1) {
-                        v
-                    } else {
-                        return Err(ParseError::NoSuchVertex(line, v1));
-                    };
-                    let v2 = if let Some(&v) = vertices.get(v2) {
-                        v
-                    } else {
-                        return Err(ParseError::NoSuchVertex(line, v2));
-                    };
-                    let v3 = if let Some(&v) = vertices.get(v3) {
-                        v
-                    } else {
-                        return Err(ParseError::NoSuchVertex(line, v3));
-                    };
-                    let tri = Triangle {
-                        vertices: (v1, v2, v3),
-                        normal: math::normal_from_points(v1, v2, v3),
-                        material: material.clone(),
-                    };
-                    scene.objects.push(DynamicRenderable::Triangle(tri));
+            },
+        };
+        self.builder.add_mesh(mesh);
+    }
+
+    /// Handles a `define` directive.
+    fn define(&mut self, directive: &Directive, path: Option<&Path>) {
+        let err = match substitute(&self.defines, directive) {
+            Ok(ref args) if args.is_empty() => {
+                directive.error(None, ParseErrorKind::TooFewArguments(0))
+            }
+            Ok(args) => {
+                let (name, token) = args[0];
+                if is_identifier(name) {
+                    let values = args[1..].iter().map(|&(arg, _)| arg.to_string()).collect();
+                    self.defines.insert(name.to_string(), values);
+                    return;
                 }
-                Line::Vertex(x, y, z) => vertices.push(Vector3::new(x, y, z)),
+                directive.error(Some(token), ParseErrorKind::BadArgument(ArgKind::Name))
             }
+            Err((token, kind)) => directive.error(token, kind),
+        };
+        self.error(err, path);
+    }
+
+    /// Records an error that occurred in the given file.
+    fn error(&mut self, mut err: ParseError, path: Option<&Path>) {
+        if err.file.is_none() {
+            err.file = path.map(Path::to_path_buf);
+        }
+        self.errors.push(err);
+    }
+
+    /// Returns the scene, or all the errors that occurred while parsing it.
+    fn finish(self) -> Result<Scene, ParseErrors> {
+        if self.errors.is_empty() {
+            Ok(self.builder.finish())
+        } else {
+            Err(ParseErrors(self.errors))
         }
-        Ok(scene)
     }
 }
 
-fn convert_color(r: f32, g: f32, b: f32) -> Rgb<f32> {
-    Rgb {
-        data: [clamp(r, 0.0, 1.0), clamp(g, 0.0, 1.0), clamp(b, 0.0, 1.0)],
+/// Returns the path a directive that takes one refers to, which is relative
+/// to the directory of the file the directive is in, if any.
+fn resolve(directive: &Directive, path: Option<&Path>) -> Result<PathBuf, DirectiveError> {
+    let arg = directive.path_arg()?;
+    let dir = path.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+    Ok(dir.join(arg))
+}
+
+/// Returns the arguments of a directive, replacing variables with their
+/// values. Each argument is paired with the token it came from, which is the
+/// variable's token for arguments that came from a variable.
+fn substitute<'a>(
+    defines: &'a HashMap<String, Vec<String>>,
+    directive: &'a Directive,
+) -> Result<Vec<(&'a str, Token)>, DirectiveError> {
+    let mut args = Vec::with_capacity(directive.args().len());
+    for &token in directive.args() {
+        let text = directive.text(token);
+        let name = match text.strip_prefix('$') {
+            Some(name) if !directive.takes_path() => name,
+            _ => {
+                args.push((text, token));
+                continue;
+            }
+        };
+        match defines.get(name) {
+            Some(values) => args.extend(values.iter().map(|v| (&**v, token))),
+            None => {
+                let kind = ParseErrorKind::UndefinedVariable(name.to_string());
+                return Err((Some(token), kind));
+            }
+        }
     }
+    Ok(args)
+}
+
+/// Returns whether a string is a valid variable name.
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Canonicalizes a path for detecting include cycles, falling back to the path
+/// itself if it can't be canonicalized.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn read_file(path: &Path) -> IoResult<String> {
+    let mut buf = String::new();
+    File::open(path)?.read_to_string(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads a file in a structured format with the given function, setting the
+/// file of any errors to the path.
+pub(crate) fn read_structured<T, F>(path: &Path, format: Format, parse: F) -> Result<T, ParseErrors>
+where
+    F: FnOnce(&str, Format) -> Result<T, ParseErrors>,
+{
+    let input = read_file(path).map_err(|err| ParseErrors(vec![ParseError::io(path, err)]))?;
+    parse(&input, format).map_err(|mut errs| {
+        for err in &mut errs.0 {
+            err.file = Some(path.to_path_buf());
+        }
+        errs
+    })
 }
 
 /// A single (non-comment) line of the scene file.
@@ -186,9 +320,13 @@ fn convert_color(r: f32, g: f32, b: f32) -> Rgb<f32> {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Line {
     AmbientLight(f32, f32, f32),
+    AttenuationDistance(f32),
+    BackMaterial(f32, f32, f32, f32, f32, f32, f32, f32, f32, f32),
     Background(f32, f32, f32),
     Camera(f32, f32, f32, f32, f32, f32, f32, f32, f32, f32),
     DirectionalLight(f32, f32, f32, f32, f32, f32, f32),
+    Emissive(f32, f32, f32, f32),
+    LightSamples(usize),
     Material(
         f32,
         f32,
@@ -206,38 +344,70 @@ pub enum Line {
         f32,
     ),
     MaxDepth(usize),
+    MeshTransform(f32, f32, f32, f32),
     MaxNormals(usize),
     MaxVertices(usize),
     Normal(f32, f32, f32),
     NormalTriangle(usize, usize, usize, usize, usize, usize),
     Output(PathBuf),
     Plane(f32, f32, f32, f32, f32, f32),
-    PointLight(f32, f32, f32, f32, f32, f32, f32),
+    PointLight(f32, f32, f32, f32, f32, f32, f32, Option<Attenuation>),
     Resolution(u32, u32),
+    Samples(usize),
+    SingleSided,
     Sphere(f32, f32, f32, f32),
-    SpotLight(f32, f32, f32, f32, f32, f32, f32, f32, f32, f32, f32, f32),
+    SpotLight(
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        f32,
+        Option<Attenuation>,
+    ),
+    TransparentBackground,
     Triangle(usize, usize, usize),
     Vertex(f32, f32, f32),
+    VertexColors,
 }
 
-/// An error while parsing.
-#[derive(Clone, Debug, PartialEq)]
-pub enum ParseError {
-    /// An error returned by a Nom parser.
-    Nom(Err),
-
-    /// A non-existent normal was referenced.
-    NoSuchNormal(Line, usize),
-
-    /// A non-existent vertex was referenced.
-    NoSuchVertex(Line, usize),
-
-    /// There was unparsed input left after parsing the string.
-    Unparsed(String),
-}
-
-impl From<Err> for ParseError {
-    fn from(e: Err) -> ParseError {
-        ParseError::Nom(e)
+impl Line {
+    /// Returns the name of the directive this line was parsed from.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Line::AmbientLight(..) => "ambient_light",
+            Line::AttenuationDistance(..) => "attenuation_distance",
+            Line::BackMaterial(..) => "back_material",
+            Line::Background(..) => "background",
+            Line::Camera(..) => "camera",
+            Line::DirectionalLight(..) => "directional_light",
+            Line::Emissive(..) => "emissive",
+            Line::LightSamples(..) => "light_samples",
+            Line::Material(..) => "material",
+            Line::MaxDepth(..) => "max_depth",
+            Line::MeshTransform(..) => "mesh_transform",
+            Line::MaxNormals(..) => "max_normals",
+            Line::MaxVertices(..) => "max_vertices",
+            Line::Normal(..) => "normal",
+            Line::NormalTriangle(..) => "normal_triangle",
+            Line::Output(..) => "output_image",
+            Line::Plane(..) => "plane",
+            Line::PointLight(..) => "point_light",
+            Line::Resolution(..) => "film_resolution",
+            Line::Samples(..) => "samples",
+            Line::SingleSided => "single_sided",
+            Line::Sphere(..) => "sphere",
+            Line::SpotLight(..) => "spot_light",
+            Line::TransparentBackground => "transparent_background",
+            Line::Triangle(..) => "triangle",
+            Line::Vertex(..) => "vertex",
+            Line::VertexColors => "vertex_colors",
+        }
     }
 }
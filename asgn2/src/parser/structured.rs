@@ -0,0 +1,129 @@
+use std::io::{Error as IoError, ErrorKind};
+use std::path::Path;
+
+use cgmath::InnerSpace;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use serde_yaml;
+use toml;
+
+use parser::{ParseError, ParseErrorKind, ParseErrors};
+use scene::Scene;
+
+/// A structured format that scenes can be described in, as an alternative to
+/// the scene file format.
+///
+/// The structure mirrors `Scene`. Vectors and colors are written as arrays of
+/// three numbers, and lights and objects have a `type` field naming their
+/// kind, e.g. `{"type": "sphere", "position": [0, 0, 2], "radius": 1}`. Any
+/// field of the scene or of a material may be omitted to use its default.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// JSON.
+    Json,
+
+    /// TOML.
+    Toml,
+
+    /// YAML.
+    Yaml,
+}
+
+impl Format {
+    /// Picks the format implied by the extension of the given path, or `None`
+    /// if the path isn't a structured scene description, in which case it
+    /// should be parsed as a scene file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Format> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match &*ext {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+}
+
+impl Scene {
+    /// Parses a scene from a structured description.
+    pub fn from_structured(input: &str, format: Format) -> Result<Scene, ParseErrors> {
+        let mut scene: Scene = deserialize(input, format)?;
+
+        // As in the scene file format, the camera vectors needn't be
+        // normalized.
+        scene.camera_direction = scene.camera_direction.normalize();
+        scene.camera_up = scene.camera_up.normalize();
+        Ok(scene)
+    }
+
+    /// Writes the scene as a structured description.
+    pub fn to_structured(&self, format: Format) -> Result<String, IoError> {
+        serialize(self, format)
+    }
+}
+
+/// Deserializes a value from a structured description, reporting errors at
+/// their location in the input.
+pub(crate) fn deserialize<T: DeserializeOwned>(
+    input: &str,
+    format: Format,
+) -> Result<T, ParseErrors> {
+    let result = match format {
+        Format::Json => serde_json::from_str(input).map_err(|err| {
+            let offset = offset_of(input, err.line(), err.column());
+            let msg = strip_location(err.to_string());
+            ParseError::new(input, offset, 0, None, ParseErrorKind::Structured(msg))
+        }),
+        Format::Toml => toml::from_str(input).map_err(|err| {
+            let span = err.span().unwrap_or(0..0);
+            let kind = ParseErrorKind::Structured(err.message().to_string());
+            ParseError::new(input, span.start, span.len(), None, kind)
+        }),
+        Format::Yaml => serde_yaml::from_str(input).map_err(|err| {
+            let offset = err.location().map_or(0, |l| l.index());
+            let msg = strip_location(err.to_string());
+            ParseError::new(input, offset, 0, None, ParseErrorKind::Structured(msg))
+        }),
+    };
+    result.map_err(|err| ParseErrors(vec![err]))
+}
+
+/// Serializes a value as a structured description.
+pub(crate) fn serialize<T: Serialize>(value: &T, format: Format) -> Result<String, IoError> {
+    let result = match format {
+        Format::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+        Format::Toml => toml::to_string(value).map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+    };
+    result.map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+}
+
+/// Converts a line and column, both starting from 1, to a byte offset into the
+/// input. Out-of-range positions are clamped to the input.
+fn offset_of(input: &str, line: usize, column: usize) -> usize {
+    let line_start = match line {
+        0 | 1 => 0,
+        n => input
+            .match_indices('\n')
+            .nth(n - 2)
+            .map_or(input.len(), |(i, _)| i + 1),
+    };
+    let line_len = input[line_start..]
+        .find('\n')
+        .unwrap_or(input.len() - line_start);
+    let mut offset = line_start + column.saturating_sub(1).min(line_len);
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Removes the " at line L column C" suffix from an error message, since the
+/// `ParseError` gives the location itself.
+fn strip_location(mut msg: String) -> String {
+    if let Some(i) = msg.rfind(" at line ") {
+        msg.truncate(i);
+    }
+    msg
+}
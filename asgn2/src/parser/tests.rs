@@ -1,8 +1,16 @@
-use cgmath::Vector3;
-use image::Pixel;
-use material::Material;
-use renderable::{DynamicRenderable, Sphere};
+use std::path::Path;
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Vector3};
+use image::{Pixel, Rgb};
+use light::{Attenuation, Directional, DynamicLight, Point, Spot};
+use material::{BackFace, Material, Sides};
+use mesh::Mesh;
+use parser::{ArgKind, Format, ParseErrorKind};
+use proptest::prelude::*;
+use renderable::{DynamicRenderable, Plane, Sphere};
 use scene::Scene;
+use util::BLACK;
 
 #[test]
 fn integration() {
@@ -13,6 +21,7 @@ fn integration() {
         Scene {
             ambient_light: Pixel::from_channels(0.1, 0.1, 0.1, 1.0),
             background: Pixel::from_channels(0.0, 0.0, 0.0, 1.0),
+            transparent_background: false,
             camera_direction: Vector3::new(0.0, 0.0, 1.0),
             camera_half_angle_tan: 1.0,
             camera_position: Vector3::new(0.0, 0.0, 0.0),
@@ -20,6 +29,10 @@ fn integration() {
             height: 1080,
             lights: vec![],
             max_collisions: 5,
+            samples: 1,
+            light_samples: 16,
+            meshes: vec![],
+            materials: vec![],
             objects: vec![DynamicRenderable::Sphere(Sphere {
                 material: Material {
                     ambient: Pixel::from_channels(1.0, 1.0, 1.0, 1.0),
@@ -28,6 +41,11 @@ fn integration() {
                     phong: 5.0,
                     transmissive: Pixel::from_channels(0.0, 0.0, 0.0, 1.0),
                     ior: 1.0,
+                    emissive: Pixel::from_channels(0.0, 0.0, 0.0, 1.0),
+                    emissive_intensity: 1.0,
+                    attenuation_distance: None,
+                    sides: Sides::Both,
+                    back: None,
                 },
                 position: Vector3::new(0.0, 0.0, 2.0),
                 radius: 1.0,
@@ -37,3 +55,590 @@ fn integration() {
         }
     );
 }
+
+#[test]
+fn errors() {
+    let input =
+        "camera 0 0 0\n  0 0 1\n\t0 1 x\n  45\nsphree 1 2 3 4\nsphere 0 0 2 1 5\ntriangle 0 1 2\n";
+    let errs = input.parse::<Scene>().unwrap_err().0;
+    let summary = errs
+        .iter()
+        .map(|e| {
+            (
+                e.line,
+                e.column,
+                e.directive.as_ref().unwrap().as_str(),
+                e.kind.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (3, 6, "camera", ParseErrorKind::BadArgument(ArgKind::Float)),
+            (5, 1, "sphree", ParseErrorKind::UnknownDirective),
+            (6, 16, "sphere", ParseErrorKind::TooManyArguments),
+            (7, 1, "triangle", ParseErrorKind::NoSuchVertex(0)),
+        ]
+    );
+    assert_eq!(errs[0].source_line, "\t0 1 x");
+    assert_eq!(
+        errs[2].to_string(),
+        "line 6, column 16: unexpected argument; `sphere` takes 4 arguments\n6 | sphere 0 0 2 1 5\n  |                ^"
+    );
+}
+
+#[test]
+fn shared_lines() {
+    // As with the nom grammar this parser replaced, several directives can
+    // share a line, and `#` only starts a comment at the start of a word.
+    let one_per_line: Scene = "film_resolution 4 3\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\n\
+                               triangle 0 1 2\nsphere 0 0 2 1\n"
+        .parse()
+        .unwrap();
+    let input = "film_resolution 4 3 vertex 0 0 0 vertex 1 0 0\nvertex 0 1 0 triangle 0 1 2 \
+                 sphere 0 0 2 1 # a comment\n";
+    assert_eq!(input.parse::<Scene>().unwrap(), one_per_line);
+    let input = "film_resolution 4 3 # a comment\nvertex 0 0 0 vertex 1 0 0 vertex 0 1 0\n\
+                 triangle 0 1 2 sphere 0\n  0 2 1\n";
+    assert_eq!(input.parse::<Scene>().unwrap(), one_per_line);
+
+    let scene: Scene = "define red 1 0 0 ambient_light $red\n".parse().unwrap();
+    assert_eq!(
+        scene.ambient_light,
+        Pixel::from_channels(1.0, 0.0, 0.0, 1.0)
+    );
+
+    // Errors point into the directive they're in.
+    let errs = "sphere 0 0 2 1 sphere 0 0 2 1 5\n"
+        .parse::<Scene>()
+        .unwrap_err()
+        .0;
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].kind, ParseErrorKind::TooManyArguments);
+    assert_eq!((errs[0].line, errs[0].column), (1, 31));
+}
+
+#[test]
+fn paths() {
+    // Paths are the rest of the line, `#`s and all, as they were in the nom
+    // grammar.
+    let scene: Scene = "output_image renders/#1.png\n".parse().unwrap();
+    assert_eq!(scene.output_image, Some("renders/#1.png".into()));
+    let scene: Scene = "sphere 0 0 2 1 output_image a.png # b\n".parse().unwrap();
+    assert_eq!(scene.output_image, Some("a.png # b".into()));
+    assert_eq!(scene.objects.len(), 1);
+    let scene: Scene = "output_image $out.png\n".parse().unwrap();
+    assert_eq!(scene.output_image, Some("$out.png".into()));
+
+    let errs = "mesh missing#1.ply\n".parse::<Scene>().unwrap_err().0;
+    match errs[0].kind {
+        ParseErrorKind::Io(ref path, _) => assert_eq!(path, Path::new("missing#1.ply")),
+        ref kind => panic!("Expected an I/O error, got {:?}", kind),
+    }
+    let errs = "include\n".parse::<Scene>().unwrap_err().0;
+    assert_eq!(errs[0].kind, ParseErrorKind::TooFewArguments(0));
+}
+
+#[test]
+fn define() {
+    let input = "define red 1 0 0\ndefine both $red $red\ndefine dull 0 0 0\n  5 0 0 0 1\nmaterial $both $dull\nsphere 0 0 2 1\n";
+    let scene: Scene = input.parse().unwrap();
+    let material = match scene.objects[0] {
+        DynamicRenderable::Sphere(ref s) => s.material,
+        ref obj => panic!("Expected a sphere, got {:?}", obj),
+    };
+    assert_eq!(material.ambient, Pixel::from_channels(1.0, 0.0, 0.0, 1.0));
+    assert_eq!(material.diffuse, Pixel::from_channels(1.0, 0.0, 0.0, 1.0));
+    assert_eq!(material.specular, Pixel::from_channels(0.0, 0.0, 0.0, 1.0));
+
+    let errs = "ambient_light $blue\n".parse::<Scene>().unwrap_err().0;
+    assert_eq!(errs.len(), 1);
+    assert_eq!(
+        errs[0].kind,
+        ParseErrorKind::UndefinedVariable("blue".to_string())
+    );
+    assert_eq!((errs[0].line, errs[0].column), (1, 15));
+}
+
+#[test]
+fn meshes() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/meshes.scn");
+    let scene = Scene::from_file(path).unwrap();
+    // Two octahedra and a pyramid, the octahedra sharing a material.
+    assert_eq!(scene.objects.len(), 1);
+    assert_eq!(scene.meshes.len(), 3);
+    assert_eq!(scene.materials.len(), 2);
+    let materials = scene.meshes.iter().map(|m| m.material).collect::<Vec<_>>();
+    assert_eq!(materials, [0, 0, 1]);
+    let triangles = scene
+        .meshes
+        .iter()
+        .flat_map(|m| m.triangles(scene.materials[m.material]))
+        .collect::<Vec<_>>();
+    assert_eq!(triangles.len(), 20);
+
+    // The octahedra are moved and scaled, and colored by their vertices.
+    let v = Vector3::new;
+    assert_eq!(triangles[0].vertices.0, v(-1.5, 1.0, 0.0));
+    assert_eq!(triangles[8].vertices.0, v(3.1, 0.6, 0.0));
+    let diffuse = |i: usize| triangles[i].material.diffuse.data;
+    assert_eq!(diffuse(0), [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+    assert_eq!(diffuse(8), diffuse(0));
+    assert_eq!(diffuse(16), [0.8, 0.6, 0.3]);
+    assert!(scene.meshes[2].colors.is_none());
+
+    // Moving the octahedra gives each its own positions, but they share the
+    // rest of the file's geometry.
+    let (a, b) = (&scene.meshes[0], &scene.meshes[1]);
+    assert!(!Arc::ptr_eq(&a.positions, &b.positions));
+    assert!(Arc::ptr_eq(&a.faces, &b.faces));
+    assert!(Arc::ptr_eq(
+        a.colors.as_ref().unwrap(),
+        b.colors.as_ref().unwrap()
+    ));
+
+    // Structured formats keep meshes as they are. The scene file format
+    // writes uncolored meshes as runs of triangles, which are read back as
+    // meshes, but has to write colored triangles one material at a time.
+    for &format in &[Format::Json, Format::Toml, Format::Yaml] {
+        let written = scene.to_structured(format).unwrap();
+        assert_round_trip(&scene, &Scene::from_structured(&written, format).unwrap());
+    }
+    let written = scene.to_string().parse::<Scene>().unwrap();
+    assert_eq!(written.objects.len(), 1);
+    let faces = written.meshes.iter().map(|m| m.faces.len()).sum::<usize>();
+    assert_eq!(faces, 20);
+    let pyramid = written.meshes.last().unwrap();
+    assert_eq!(pyramid.positions, scene.meshes[2].positions);
+    assert_eq!(pyramid.faces, scene.meshes[2].faces);
+    assert_eq!(
+        written.materials[pyramid.material],
+        scene.materials[scene.meshes[2].material]
+    );
+
+    let errs = "mesh missing.ply\n".parse::<Scene>().unwrap_err().0;
+    match errs[0].kind {
+        ParseErrorKind::Io(ref path, _) => assert_eq!(path, Path::new("missing.ply")),
+        ref kind => panic!("Expected an I/O error, got {:?}", kind),
+    }
+    let errs = "mesh scene.obj\n".parse::<Scene>().unwrap_err().0;
+    assert_eq!(
+        errs[0].to_string(),
+        "line 1, column 1: couldn't read scene.obj: meshes must be .ply or .stl files\n\
+         1 | mesh scene.obj\n  | ^^^^^^^^^^^^^^"
+    );
+}
+
+#[test]
+fn write_example_scenes() {
+    for input in &[
+        include_str!("../../scenes/class1.scn"),
+        include_str!("../../scenes/sphere.scn"),
+        include_str!("../../scenes/testing.scn"),
+    ] {
+        let scene: Scene = input.parse().unwrap();
+        let written = scene.to_string();
+        assert_round_trip(&scene, &written.parse().unwrap());
+        assert_eq!(written.parse::<Scene>().unwrap().to_string(), written);
+    }
+}
+
+#[test]
+fn structured_errors() {
+    let input = "{\"width\": 640,\n \"objects\": [{\"type\": \"cube\"}]}";
+    let errs = Scene::from_structured(input, Format::Json).unwrap_err().0;
+    assert_eq!(errs.len(), 1);
+    assert_eq!((errs[0].line, errs[0].column), (2, 28));
+    match errs[0].kind {
+        ParseErrorKind::Structured(ref msg) => assert!(msg.starts_with("unknown variant `cube`")),
+        ref kind => panic!("Expected a structured error, got {:?}", kind),
+    }
+
+    let errs = Scene::from_structured("height: -1\n", Format::Yaml)
+        .unwrap_err()
+        .0;
+    assert_eq!((errs[0].line, errs[0].column), (1, 9));
+}
+
+#[test]
+fn attenuation() {
+    let input = "point_light 1 1 1 0 0 0\npoint_light 1 1 1 0 0 0 2 1\n\
+                 spot_light 1 1 1 0 0 0 0 0 1 10 20 1 0 0 1\n";
+    let scene: Scene = input.parse().unwrap();
+    let attenuation = |l: &DynamicLight| match *l {
+        DynamicLight::Point(ref l) => l.attenuation,
+        DynamicLight::Spot(ref l) => l.attenuation,
+        _ => unreachable!(),
+    };
+    assert_eq!(attenuation(&scene.lights[0]), Attenuation::default());
+    let constant = Attenuation {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+    assert_eq!(attenuation(&scene.lights[1]), constant);
+    assert_eq!(attenuation(&scene.lights[2]), Attenuation::INVERSE_SQUARE);
+
+    let errs = "point_light 1 1 1 0 0 0 1 0 0 1 0\n"
+        .parse::<Scene>()
+        .unwrap_err()
+        .0;
+    assert_eq!(errs[0].kind, ParseErrorKind::TooManyArguments);
+    assert_eq!(errs[0].arity.unwrap().to_string(), "6 to 10 arguments");
+}
+
+#[test]
+fn triangle_runs() {
+    // Runs of triangles become meshes, which end at any directive other than
+    // those defining vertices and normals, or when the triangles switch
+    // between having normals and not.
+    let input = "vertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\ntriangle 0 1 2\nvertex 0 1 0\n\
+                 triangle 0 2 3\nsphere 0 0 5 1\ntriangle 3 2 1\nnormal 0 0 1\n\
+                 normal_triangle 0 1 2 0 0 0\n";
+    let scene: Scene = input.parse().unwrap();
+    assert_eq!(scene.objects.len(), 1);
+    assert_eq!(scene.materials, [Material::default()]);
+    let v = Vector3::new;
+    let (square, tri, lit) = (&scene.meshes[0], &scene.meshes[1], &scene.meshes[2]);
+    assert_eq!(scene.meshes.len(), 3);
+    assert_eq!(
+        *square.positions,
+        [
+            v(0.0, 0.0, 0.0),
+            v(1.0, 0.0, 0.0),
+            v(1.0, 1.0, 0.0),
+            v(0.0, 1.0, 0.0)
+        ]
+    );
+    assert_eq!(*square.faces, [[0, 1, 2], [0, 2, 3]]);
+    assert_eq!(*tri.faces, [[0, 1, 2]]);
+    assert_eq!(tri.positions[0], v(0.0, 1.0, 0.0));
+    assert!(square.normals.is_none() && tri.normals.is_none());
+    assert_eq!(**lit.normals.as_ref().unwrap(), [v(0.0, 0.0, 1.0); 3]);
+
+    // Each vertex is stored once per mesh, with each normal it's used with.
+    let input = "vertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nnormal 0 0 1\nnormal 0 0 -1\n\
+                 normal_triangle 0 1 2 0 0 0\nnormal_triangle 0 2 1 1 1 1\n\
+                 normal_triangle 1 0 2 0 0 0\n";
+    let scene: Scene = input.parse().unwrap();
+    assert_eq!(scene.meshes.len(), 1);
+    assert_eq!(scene.meshes[0].positions.len(), 6);
+    assert_eq!(*scene.meshes[0].faces, [[0, 1, 2], [3, 4, 5], [1, 0, 2]]);
+}
+
+#[test]
+fn max_vertices() {
+    let input = "max_vertices 2\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\ntriangle 0 1 2\n";
+    let errs = input.parse::<Scene>().unwrap_err().0;
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].kind, ParseErrorKind::TooManyVertices(2));
+    assert_eq!(errs[0].line, 4);
+
+    let input = "max_vertices 3\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\ntriangle 0 1 2\n";
+    assert_eq!(input.parse::<Scene>().unwrap().meshes.len(), 1);
+}
+
+#[test]
+fn max_normals() {
+    let input = "max_normals 1\nnormal 0 0 1\nnormal 0 1 0\n";
+    let errs = input.parse::<Scene>().unwrap_err().0;
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].kind, ParseErrorKind::TooManyNormals(1));
+    assert_eq!(errs[0].line, 3);
+
+    // A maximum below the number of normals already defined is an error too.
+    let input = "normal 0 0 1\nnormal 0 1 0\nmax_normals 1\n";
+    let errs = input.parse::<Scene>().unwrap_err().0;
+    assert_eq!(errs[0].kind, ParseErrorKind::TooManyNormals(1));
+    assert_eq!(errs[0].line, 3);
+
+    let input = "max_normals 1\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nnormal 0 0 1\n\
+                 normal_triangle 0 1 2 0 0 0\n";
+    assert_eq!(input.parse::<Scene>().unwrap().meshes.len(), 1);
+}
+
+proptest! {
+    #[test]
+    fn parse_never_panics(input in "((camera|sphere|material|triangle|normal_triangle|film_resolution|define|[a-z_]+|[-+.eE0-9]+|\\$[a-z]+|#|[ \t]+|\r?\n|.))*") {
+        let _ = input.parse::<Scene>();
+    }
+
+    #[test]
+    fn round_trip(scene in arb_scene()) {
+        let parsed = scene.to_string().parse().unwrap();
+        assert_round_trip(&scene, &parsed);
+    }
+
+    #[test]
+    fn structured_round_trip(scene in arb_scene()) {
+        for &format in &[Format::Json, Format::Toml, Format::Yaml] {
+            let written = scene.to_structured(format).unwrap();
+            assert_round_trip(&scene, &Scene::from_structured(&written, format).unwrap());
+        }
+    }
+}
+
+/// Asserts that a scene was parsed back unchanged, except for the camera,
+/// which the parser normalizes and stores as the tangent of an angle, and so
+/// can only be compared approximately.
+fn assert_round_trip(expected: &Scene, actual: &Scene) {
+    let close = |a: Vector3<f32>, b: Vector3<f32>| (a - b).magnitude() <= 1e-5 * b.magnitude();
+    assert!(close(actual.camera_direction, expected.camera_direction));
+    assert!(close(actual.camera_up, expected.camera_up));
+    let (t1, t2) = (actual.camera_half_angle_tan, expected.camera_half_angle_tan);
+    assert!((t1 - t2).abs() <= 1e-5 * t2);
+    assert_eq!(
+        Scene {
+            camera_direction: expected.camera_direction,
+            camera_up: expected.camera_up,
+            camera_half_angle_tan: expected.camera_half_angle_tan,
+            ..actual.clone()
+        },
+        *expected
+    );
+}
+
+fn arb_f32() -> impl Strategy<Value = f32> {
+    -1000.0f32..1000.0
+}
+
+fn arb_vector() -> impl Strategy<Value = Vector3<f32>> {
+    (arb_f32(), arb_f32(), arb_f32()).prop_map(|(x, y, z)| Vector3::new(x, y, z))
+}
+
+fn arb_direction() -> impl Strategy<Value = Vector3<f32>> {
+    arb_vector()
+        .prop_filter("zero vector", |v| v.magnitude() > 1.0)
+        .prop_map(|v| v.normalize())
+}
+
+fn arb_color() -> impl Strategy<Value = Rgb<f32>> {
+    (0.0f32..=1.0, 0.0f32..=1.0, 0.0f32..=1.0)
+        .prop_map(|(r, g, b)| Pixel::from_channels(r, g, b, 1.0))
+}
+
+fn arb_material() -> impl Strategy<Value = Material> {
+    (
+        arb_color(),
+        arb_color(),
+        arb_color(),
+        0.0f32..100.0,
+        arb_color(),
+        1.0f32..3.0,
+        prop_oneof![Just(BLACK), arb_color()],
+        0.0f32..10.0,
+        proptest::option::of(0.01f32..100.0),
+        prop_oneof![Just(Sides::Both), Just(Sides::Front)],
+        proptest::option::of(arb_back_face()),
+    )
+        .prop_map(
+            |(
+                ambient,
+                diffuse,
+                specular,
+                phong,
+                transmissive,
+                ior,
+                emissive,
+                emissive_intensity,
+                attenuation_distance,
+                sides,
+                back,
+            )| Material {
+                ambient,
+                diffuse,
+                specular,
+                phong,
+                transmissive,
+                ior,
+                emissive,
+                emissive_intensity,
+                attenuation_distance,
+                sides,
+                back,
+            },
+        )
+}
+
+fn arb_back_face() -> impl Strategy<Value = BackFace> {
+    (arb_color(), arb_color(), arb_color(), 0.0f32..100.0).prop_map(
+        |(ambient, diffuse, specular, phong)| BackFace {
+            ambient,
+            diffuse,
+            specular,
+            phong,
+        },
+    )
+}
+
+fn arb_light() -> impl Strategy<Value = DynamicLight> {
+    prop_oneof![
+        (arb_color(), arb_vector(), arb_f32()).prop_map(|(color, direction, intensity)| {
+            DynamicLight::Directional(Directional {
+                color,
+                direction,
+                intensity,
+            })
+        }),
+        (arb_color(), arb_vector(), arb_f32(), arb_attenuation()).prop_map(
+            |(color, position, intensity, attenuation)| {
+                DynamicLight::Point(Point {
+                    color,
+                    position,
+                    intensity,
+                    attenuation,
+                })
+            }
+        ),
+        (
+            arb_color(),
+            arb_vector(),
+            arb_vector(),
+            0.0f32..90.0,
+            0.0f32..90.0,
+            arb_f32(),
+            arb_attenuation()
+        )
+            .prop_map(
+                |(color, position, direction, falloff_angle, max_angle, intensity, attenuation)| {
+                    DynamicLight::Spot(Spot {
+                        color,
+                        position,
+                        direction,
+                        intensity,
+                        falloff_angle,
+                        max_angle,
+                        attenuation,
+                    })
+                }
+            ),
+    ]
+}
+
+fn arb_attenuation() -> impl Strategy<Value = Attenuation> {
+    prop_oneof![
+        Just(Attenuation::default()),
+        (0.0f32..10.0, 0.0f32..10.0, 0.0f32..10.0).prop_map(|(constant, linear, quadratic)| {
+            Attenuation {
+                constant,
+                linear,
+                quadratic,
+            }
+        }),
+    ]
+}
+
+fn arb_object() -> impl Strategy<Value = DynamicRenderable> {
+    // Objects often share materials, which is worth testing.
+    let material = prop_oneof![Just(Material::default()), arb_material()];
+    prop_oneof![
+        (arb_vector(), arb_vector(), material.clone()).prop_map(|(point, normal, material)| {
+            DynamicRenderable::Plane(Plane {
+                point,
+                normal,
+                material,
+            })
+        }),
+        (arb_vector(), 0.0f32..100.0, material).prop_map(|(position, radius, material)| {
+            DynamicRenderable::Sphere(Sphere {
+                position,
+                radius,
+                material,
+            })
+        }),
+    ]
+}
+
+/// Returns a mesh as it's parsed from `triangle` or `normal_triangle`
+/// directives, with its vertices in the order its faces first use them, and
+/// its material. The material index is left for `arb_scene` to fill in.
+fn arb_mesh() -> impl Strategy<Value = (Mesh, Material)> {
+    let material = prop_oneof![Just(Material::default()), arb_material()];
+    let vertices = proptest::collection::vec(arb_vector(), 1..5).prop_flat_map(|positions| {
+        let n = positions.len();
+        let faces = proptest::collection::vec([0..n as u32, 0..n as u32, 0..n as u32], 1..5);
+        let normals = proptest::option::of(proptest::collection::vec(arb_direction(), n));
+        (Just(positions), faces, normals)
+    });
+    (vertices, material).prop_map(|((positions, faces, normals), material)| {
+        // Renumber the vertices in the order the faces use them, dropping
+        // any that aren't used.
+        let mut order: Vec<u32> = Vec::new();
+        let faces = faces
+            .iter()
+            .map(|face| {
+                face.map(|v| match order.iter().position(|&u| u == v) {
+                    Some(i) => i as u32,
+                    None => {
+                        order.push(v);
+                        order.len() as u32 - 1
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let pick = |values: &[Vector3<f32>]| -> Vec<Vector3<f32>> {
+            order.iter().map(|&v| values[v as usize]).collect()
+        };
+        let mesh = Mesh {
+            positions: Arc::new(pick(&positions)),
+            normals: normals.map(|normals| Arc::new(pick(&normals))),
+            colors: None,
+            faces: Arc::new(faces),
+            material: 0,
+        };
+        (mesh, material)
+    })
+}
+
+prop_compose! {
+    fn arb_scene()(
+        camera_position in arb_vector(),
+        camera_direction in arb_direction(),
+        camera_up in arb_direction(),
+        half_angle in 1.0f32..89.0,
+        width in 1u32..4096,
+        height in 1u32..4096,
+        output_image in proptest::option::of("[a-z][a-z0-9_ ./#]{0,16}"),
+        ambient_light in arb_color(),
+        background in arb_color(),
+        transparent_background in any::<bool>(),
+        lights in proptest::collection::vec(arb_light(), 0..4),
+        objects in proptest::collection::vec(arb_object(), 0..8),
+        meshes in proptest::collection::vec(arb_mesh(), 0..4),
+        max_collisions in 0usize..100,
+        samples in 1usize..16,
+        light_samples in 1usize..64,
+    ) -> Scene {
+        let mut materials = Vec::new();
+        let meshes = meshes
+            .into_iter()
+            .map(|(mesh, material)| {
+                let i = materials.iter().position(|&m| m == material).unwrap_or_else(|| {
+                    materials.push(material);
+                    materials.len() - 1
+                });
+                Mesh { material: i, ..mesh }
+            })
+            .collect();
+        Scene {
+            camera_position,
+            camera_direction,
+            camera_up,
+            camera_half_angle_tan: half_angle.to_radians().tan(),
+            width,
+            height,
+            output_image: output_image.map(|p| p.trim().into()),
+            ambient_light,
+            background,
+            transparent_background,
+            lights,
+            objects,
+            meshes,
+            materials,
+            max_collisions,
+            samples,
+            light_samples,
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Error as FmtError, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{BufWriter, Result as IoResult, Write};
+use std::path::Path;
+
+use cgmath::Vector3;
+use image::Rgb;
+
+use light::{Attenuation, DynamicLight};
+use material::{Material, Sides};
+use parser::Format;
+use renderable::DynamicRenderable;
+use scene::Scene;
+use util::BLACK;
+
+/// Writes the scene in the scene file format, such that parsing the output
+/// gives back the same scene.
+///
+/// The output is canonical: the camera and image settings come first, then
+/// the lights, then the objects, then the meshes. A `material` directive,
+/// followed by `emissive`, `attenuation_distance`, `single_sided` and
+/// `back_material` if they apply, is written when the material changes and
+/// before each mesh. Each distinct vertex of the objects is written once, just
+/// before the first triangle that uses it.
+///
+/// Triangle objects are written with `triangle`, so their normals are
+/// recomputed from their vertices, and they're parsed back as meshes. Meshes
+/// are written as their vertices and normals followed by their faces, except
+/// for those with vertex colors, which are written as separate triangles. A
+/// mesh's vertices are numbered in the order its faces first use them when
+/// it's parsed back.
+impl Display for Scene {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        writeln!(
+            fmt,
+            "camera {} {} {} {}",
+            Vec3(self.camera_position),
+            Vec3(self.camera_direction),
+            Vec3(self.camera_up),
+            self.camera_half_angle_tan.atan().to_degrees()
+        )?;
+        writeln!(fmt, "film_resolution {} {}", self.width, self.height)?;
+        if let Some(ref path) = self.output_image {
+            writeln!(fmt, "output_image {}", path.display())?;
+        }
+        writeln!(fmt, "background {}", Color(self.background))?;
+        if self.transparent_background {
+            writeln!(fmt, "transparent_background")?;
+        }
+        writeln!(fmt, "ambient_light {}", Color(self.ambient_light))?;
+        writeln!(fmt, "max_depth {}", self.max_collisions)?;
+        writeln!(fmt, "samples {}", self.samples)?;
+        writeln!(fmt, "light_samples {}", self.light_samples)?;
+
+        if !self.lights.is_empty() {
+            writeln!(fmt)?;
+        }
+        for light in &self.lights {
+            match *light {
+                DynamicLight::Directional(ref l) => writeln!(
+                    fmt,
+                    "directional_light {} {} {}",
+                    Color(l.color),
+                    Vec3(l.direction),
+                    l.intensity
+                )?,
+                DynamicLight::Point(ref l) => writeln!(
+                    fmt,
+                    "point_light {} {} {}{}",
+                    Color(l.color),
+                    Vec3(l.position),
+                    l.intensity,
+                    Coefficients(l.attenuation)
+                )?,
+                DynamicLight::Spot(ref l) => writeln!(
+                    fmt,
+                    "spot_light {} {} {} {} {} {}{}",
+                    Color(l.color),
+                    Vec3(l.position),
+                    Vec3(l.direction),
+                    l.falloff_angle,
+                    l.max_angle,
+                    l.intensity,
+                    Coefficients(l.attenuation)
+                )?,
+            }
+        }
+
+        if !self.objects.is_empty() || !self.meshes.is_empty() {
+            writeln!(fmt)?;
+        }
+        let mut state = WriteState::default();
+        for obj in &self.objects {
+            state.object(fmt, obj)?;
+        }
+        for mesh in &self.meshes {
+            let m = self.materials[mesh.material];
+            if mesh.colors.is_some() {
+                // Vertex colors can only be written as the diffuse colors of
+                // separate triangles.
+                for t in mesh.triangles(m) {
+                    state.object(fmt, &DynamicRenderable::Triangle(t))?;
+                }
+                continue;
+            }
+
+            // Writing the material, even if it's unchanged, ends the run of
+            // triangles before, so that the mesh is parsed back on its own.
+            state.material(fmt, m, true)?;
+            let (first_vertex, first_normal) = (state.vertices, state.normals);
+            for &p in mesh.positions.iter() {
+                writeln!(fmt, "vertex {}", Vec3(p))?;
+            }
+            state.vertices += mesh.positions.len();
+            for &n in mesh.normals.iter().flat_map(|n| n.iter()) {
+                writeln!(fmt, "normal {}", Vec3(n))?;
+            }
+            state.normals += mesh.normals.as_ref().map_or(0, |n| n.len());
+            for face in mesh.faces.iter() {
+                let [i, j, k] = face.map(|v| v as usize);
+                let (v1, v2, v3) = (first_vertex + i, first_vertex + j, first_vertex + k);
+                if mesh.normals.is_some() {
+                    let (n1, n2, n3) = (first_normal + i, first_normal + j, first_normal + k);
+                    writeln!(
+                        fmt,
+                        "normal_triangle {} {} {} {} {} {}",
+                        v1, v2, v3, n1, n2, n3
+                    )?;
+                } else {
+                    writeln!(fmt, "triangle {} {} {}", v1, v2, v3)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What's been written of a scene's objects so far.
+#[derive(Default)]
+struct WriteState {
+    /// The material last written, if any.
+    material: Option<Material>,
+
+    /// The index of each vertex written for a triangle, by the bits of its
+    /// coordinates.
+    indices: HashMap<[u32; 3], usize>,
+
+    /// The number of vertices and normals written.
+    vertices: usize,
+    normals: usize,
+}
+
+impl WriteState {
+    /// Writes an object, preceded by its material if it differs from the last
+    /// one written, and by any of its vertices that haven't been written yet.
+    fn object(&mut self, fmt: &mut Formatter, obj: &DynamicRenderable) -> FmtResult {
+        let m = match *obj {
+            DynamicRenderable::Plane(ref p) => p.material,
+            DynamicRenderable::Sphere(ref s) => s.material,
+            DynamicRenderable::Triangle(ref t) => t.material,
+        };
+        self.material(fmt, m, false)?;
+        match *obj {
+            DynamicRenderable::Plane(ref p) => {
+                writeln!(fmt, "plane {} {}", Vec3(p.point), Vec3(p.normal))
+            }
+            DynamicRenderable::Sphere(ref s) => {
+                writeln!(fmt, "sphere {} {}", Vec3(s.position), s.radius)
+            }
+            DynamicRenderable::Triangle(ref t) => {
+                let (v1, v2, v3) = t.vertices;
+                let (i1, i2, i3) = (
+                    self.vertex(fmt, v1)?,
+                    self.vertex(fmt, v2)?,
+                    self.vertex(fmt, v3)?,
+                );
+                writeln!(fmt, "triangle {} {} {}", i1, i2, i3)
+            }
+        }
+    }
+
+    /// Returns the index of a triangle's vertex, writing it first if it
+    /// hasn't been written yet.
+    fn vertex(&mut self, fmt: &mut Formatter, v: Vector3<f32>) -> Result<usize, FmtError> {
+        let key = [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()];
+        if let Some(&i) = self.indices.get(&key) {
+            return Ok(i);
+        }
+        writeln!(fmt, "vertex {}", Vec3(v))?;
+        self.indices.insert(key, self.vertices);
+        self.vertices += 1;
+        Ok(self.vertices - 1)
+    }
+
+    /// Writes the directives setting a material, unless it's the last one
+    /// written and `force` is false.
+    fn material(&mut self, fmt: &mut Formatter, m: Material, force: bool) -> FmtResult {
+        if !force && self.material == Some(m) {
+            return Ok(());
+        }
+        self.material = Some(m);
+        writeln!(
+            fmt,
+            "material {} {} {} {} {} {}",
+            Color(m.ambient),
+            Color(m.diffuse),
+            Color(m.specular),
+            m.phong,
+            Color(m.transmissive),
+            m.ior
+        )?;
+        if m.emissive != BLACK || m.emissive_intensity != 1.0 {
+            writeln!(
+                fmt,
+                "emissive {} {}",
+                Color(m.emissive),
+                m.emissive_intensity
+            )?;
+        }
+        if let Some(d) = m.attenuation_distance {
+            writeln!(fmt, "attenuation_distance {}", d)?;
+        }
+        if m.sides == Sides::Front {
+            writeln!(fmt, "single_sided")?;
+        }
+        if let Some(ref back) = m.back {
+            writeln!(
+                fmt,
+                "back_material {} {} {} {}",
+                Color(back.ambient),
+                Color(back.diffuse),
+                Color(back.specular),
+                back.phong
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Scene {
+    /// Writes the scene to a file in the scene file format, or in a
+    /// structured `Format` if the file has the extension of one.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> IoResult<()> {
+        let path = path.as_ref();
+        let mut f = BufWriter::new(File::create(path)?);
+        match Format::from_path(path) {
+            Some(format) => f.write_all(self.to_structured(format)?.as_bytes())?,
+            None => write!(f, "{}", self)?,
+        }
+        f.flush()
+    }
+}
+
+/// Displays a vector as its three components.
+struct Vec3(Vector3<f32>);
+
+impl Display for Vec3 {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{} {} {}", self.0.x, self.0.y, self.0.z)
+    }
+}
+
+/// Displays a light's attenuation coefficients, with a leading space, or
+/// nothing if the attenuation is the default.
+struct Coefficients(Attenuation);
+
+impl Display for Coefficients {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let a = self.0;
+        if a == Attenuation::default() {
+            Ok(())
+        } else {
+            write!(fmt, " {} {} {}", a.constant, a.linear, a.quadratic)
+        }
+    }
+}
+
+/// Displays a color as its three channels.
+struct Color(Rgb<f32>);
+
+impl Display for Color {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(
+            fmt,
+            "{} {} {}",
+            self.0.data[0], self.0.data[1], self.0.data[2]
+        )
+    }
+}
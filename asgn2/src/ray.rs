@@ -1,5 +1,11 @@
+use std::f32;
+
 use cgmath::{InnerSpace, Vector3};
 
+/// How many times the rounding error of a coordinate a ray leaving a surface
+/// is offset by, so that it doesn't hit the surface it left.
+const OFFSET_ULPS: f32 = 512.0;
+
 /// A ray.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Ray {
@@ -11,20 +17,36 @@ pub struct Ray {
 }
 
 impl Ray {
+    /// Creates a ray leaving a surface from the given point, in the given
+    /// direction. The origin is moved off the surface, to the side of it the
+    /// ray leaves from, by an amount that grows with the magnitude of the
+    /// point's coordinates, since so does the error in computing it.
+    pub fn leaving(point: Vector3<f32>, normal: Vector3<f32>, direction: Vector3<f32>) -> Ray {
+        let normal = normal.normalize();
+        let normal = if normal.dot(direction) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        Ray {
+            origin: point + epsilon(point) * normal,
+            direction,
+        }
+    }
+
     /// Attempts to find the collision between the row and the plane containing the given point,
     /// with the given normal.
     pub fn collide_plane(&self, point: Vector3<f32>, normal: Vector3<f32>) -> Option<f32> {
         let denom = normal.dot(self.direction);
-        if denom == 0.0 {
-            None
+        let numer = normal.dot(point - self.origin);
+        let dist = numer / denom;
+        // A ray parallel to the plane gives an infinite or NaN distance,
+        // which isn't a collision. Rays that are nearly parallel still
+        // collide, however far away.
+        if dist >= 0.0 && dist.is_finite() {
+            Some(dist)
         } else {
-            let numer = normal.dot(point - self.origin);
-            let dist = numer / denom;
-            if dist >= 0.0 {
-                Some(dist)
-            } else {
-                None
-            }
+            None
         }
     }
 
@@ -33,3 +55,10 @@ impl Ray {
         self.origin + distance * self.direction
     }
 }
+
+/// Returns a distance safely larger than the rounding error in the
+/// coordinates of a point.
+fn epsilon(point: Vector3<f32>) -> f32 {
+    let magnitude = point.x.abs().max(point.y.abs()).max(point.z.abs());
+    OFFSET_ULPS * f32::EPSILON * (1.0 + magnitude)
+}
@@ -2,6 +2,8 @@
 
 mod plane;
 mod sphere;
+#[cfg(test)]
+mod tests;
 mod triangle;
 
 use std::fmt::Debug;
@@ -13,11 +15,13 @@ use ray::Ray;
 pub use renderable::plane::Plane;
 pub use renderable::sphere::Sphere;
 pub use renderable::triangle::Triangle;
+pub(crate) use renderable::triangle::{intersect_triangle, Shear};
 
 /// Any renderable defined in this crate.
 ///
 /// Defined because it's more efficient than a trait object.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum DynamicRenderable {
     /// A plane.
     Plane(Plane),
@@ -29,6 +33,17 @@ pub enum DynamicRenderable {
     Triangle(Triangle),
 }
 
+impl DynamicRenderable {
+    /// Returns the material the object is made of, to change it.
+    pub fn material_mut(&mut self) -> &mut Material {
+        match *self {
+            DynamicRenderable::Plane(ref mut p) => &mut p.material,
+            DynamicRenderable::Sphere(ref mut s) => &mut s.material,
+            DynamicRenderable::Triangle(ref mut t) => &mut t.material,
+        }
+    }
+}
+
 impl Renderable for DynamicRenderable {
     fn collides_with(&self, ray: Ray) -> Option<f32> {
         match *self {
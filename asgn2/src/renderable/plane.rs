@@ -1,16 +1,19 @@
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector3};
 
-use material::Material;
+use material::{Material, Sides};
 use ray::Ray;
 use renderable::Renderable;
+use util;
 
 /// An infinite plane.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Plane {
     /// An arbitrary point on the plane.
+    #[serde(with = "util::serde_vector")]
     pub point: Vector3<f32>,
 
     /// The normal vector from the plane.
+    #[serde(with = "util::serde_vector")]
     pub normal: Vector3<f32>,
 
     /// The material the plane is made of.
@@ -19,6 +22,9 @@ pub struct Plane {
 
 impl Renderable for Plane {
     fn collides_with(&self, ray: Ray) -> Option<f32> {
+        if self.material.sides == Sides::Front && self.normal.dot(ray.direction) >= 0.0 {
+            return None;
+        }
         ray.collide_plane(self.point, self.normal)
     }
 
@@ -1,13 +1,15 @@
 use cgmath::{InnerSpace, Vector3};
 
-use material::Material;
+use material::{Material, Sides};
 use ray::Ray;
 use renderable::Renderable;
+use util;
 
 /// A sphere.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Sphere {
     /// The position of the center of the sphere.
+    #[serde(with = "util::serde_vector")]
     pub position: Vector3<f32>,
 
     /// The radius of the sphere.
@@ -19,29 +21,28 @@ pub struct Sphere {
 
 impl Renderable for Sphere {
     fn collides_with(&self, ray: Ray) -> Option<f32> {
+        // The quadratic is solved in the forms from chapter 7 of Ray Tracing
+        // Gems, which avoid the catastrophic cancellation of the textbook
+        // formula when the sphere is small or far away.
+        let oc = ray.origin - self.position;
         let a = ray.direction.magnitude2();
-        let b = 2.0 * ray.direction.dot(ray.origin - self.position);
-        let c = ray.origin.magnitude2() + self.position.magnitude2()
-            - 2.0 * ray.origin.dot(self.position)
-            - self.radius * self.radius;
-
-        let delta = b * b - 4.0 * a * c;
-        if delta >= 0.0 {
-            let two_a = 2.0 * a;
-            let minus_b = -b;
-            let delta_sqrt = delta.sqrt();
-
-            let t = (minus_b - delta_sqrt) / two_a;
-            if t <= 0.0 {
-                let t = (minus_b + delta_sqrt) / two_a;
-                if t <= 0.0 {
-                    None
-                } else {
-                    Some(t)
-                }
-            } else {
-                Some(t)
-            }
+        let half_b = ray.direction.dot(oc);
+        let c = oc.magnitude2() - self.radius * self.radius;
+
+        let closest = oc - (half_b / a) * ray.direction;
+        let delta = a * (self.radius * self.radius - closest.magnitude2());
+        if delta < 0.0 {
+            return None;
+        }
+
+        let q = -half_b - delta.sqrt().copysign(half_b);
+        let (t0, t1) = (c / q, q / a);
+        let (near, far) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+        if near > 0.0 {
+            Some(near)
+        } else if far > 0.0 && self.material.sides == Sides::Both {
+            // The ray starts inside the sphere, so hits the inside of it.
+            Some(far)
         } else {
             None
         }
@@ -0,0 +1,284 @@
+use cgmath::{InnerSpace, Vector3};
+use proptest::prelude::*;
+
+use material::{Material, Sides};
+use ray::Ray;
+use renderable::{Plane, Renderable, Sphere, Triangle};
+
+#[test]
+fn single_sided() {
+    let material = Material {
+        sides: Sides::Front,
+        ..Material::default()
+    };
+    let x = Vector3::new(1.0, 0.0, 0.0);
+    let y = Vector3::new(0.0, 1.0, 0.0);
+    let z = Vector3::new(0.0, 0.0, 1.0);
+    let mut triangle = triangle(-x - y, x - y, y);
+    triangle.material = material;
+    let plane = Plane {
+        point: Vector3::new(0.0, 0.0, 0.0),
+        normal: z,
+        material,
+    };
+    let sphere = Sphere {
+        position: Vector3::new(0.0, 0.0, 0.0),
+        radius: 1.0,
+        material,
+    };
+
+    // The normals point towards +z, so only rays going towards -z hit.
+    let front = Ray {
+        origin: 2.0 * z,
+        direction: -z,
+    };
+    let back = Ray {
+        origin: -2.0 * z,
+        direction: z,
+    };
+    assert_eq!(triangle.collides_with(front), Some(2.0));
+    assert_eq!(triangle.collides_with(back), None);
+    assert_eq!(plane.collides_with(front), Some(2.0));
+    assert_eq!(plane.collides_with(back), None);
+    assert_eq!(sphere.collides_with(front), Some(1.0));
+
+    // Rays from inside a sphere can only meet the back of it, so they miss a single-sided sphere,
+    // but hit a double-sided one.
+    let inside = Ray {
+        origin: Vector3::new(0.0, 0.0, 0.0),
+        direction: z,
+    };
+    assert_eq!(sphere.collides_with(inside), None);
+    let sphere = Sphere {
+        material: Material::default(),
+        ..sphere
+    };
+    assert_eq!(sphere.collides_with(inside), Some(1.0));
+}
+
+proptest! {
+    #[test]
+    fn plane_hit_miss(
+        point in arb_point(),
+        normal in arb_direction(),
+        origin in arb_point(),
+        direction in arb_direction(),
+    ) {
+        let plane = Plane {
+            point,
+            normal,
+            material: Material::default(),
+        };
+        let side = normal.dot(origin - point);
+        let facing = normal.dot(direction);
+        prop_assume!(side.abs() > 1e-2 && facing.abs() > 1e-3);
+
+        // Exactly one of a ray and its reverse hits the plane.
+        let forward = plane.collides_with(Ray { origin, direction });
+        let backward = plane.collides_with(Ray { origin, direction: -direction });
+        prop_assert_eq!(forward.is_some(), side * facing < 0.0);
+        prop_assert_eq!(forward.is_some(), backward.is_none());
+    }
+
+    #[test]
+    fn sphere_hit_miss(
+        center in arb_point(),
+        radius in 1e-2f32..50.0,
+        out in arb_direction(),
+        away in 2.0f32..10.0,
+        surface in arb_direction(),
+    ) {
+        let sphere = Sphere {
+            position: center,
+            radius,
+            material: Material::default(),
+        };
+        let origin = center + radius * away * out;
+        // Only the part of the sphere facing the origin can be hit.
+        let surface = if surface.dot(out) < 0.0 {
+            -surface
+        } else {
+            surface
+        };
+        let target = center + radius * surface;
+        prop_assume!((origin - target).dot(surface) > 0.1 * radius);
+
+        // A ray aimed at the near side of the sphere hits it there. The
+        // points are rounded to the precision of the center's coordinates, so
+        // where the ray actually hits is found in double precision.
+        let direction = (target - origin).normalize();
+        let dist = (target - origin).magnitude();
+        let ray = Ray { origin, direction };
+        let hit = sphere.collides_with(ray);
+        prop_assert!(hit.is_some());
+        assert_close(hit.unwrap(), sphere_distance(&sphere, ray), radius)?;
+
+        // The reverse of that ray points away from the sphere, so misses it.
+        let reverse = Ray {
+            origin,
+            direction: -direction,
+        };
+        prop_assert!(sphere.collides_with(reverse).is_none());
+
+        // A ray from the other side of the sphere, aimed back along the same
+        // line, hits it too.
+        let beyond = origin + (dist + 3.0 * radius) * direction;
+        let hit = sphere.collides_with(Ray { origin: beyond, direction: -direction });
+        prop_assert!(hit.is_some());
+        let p = to_f64(beyond) - f64::from(hit.unwrap()) * to_f64(direction);
+        assert_close((p - to_f64(center)).magnitude() as f32, radius, radius)?;
+    }
+
+    #[test]
+    fn triangle_hit_miss(
+        (a, b, c) in (arb_point(), arb_point(), arb_point()),
+        origin in arb_point(),
+        (u, v) in (-0.5f32..1.5, -0.5f32..1.5),
+    ) {
+        let triangle = triangle(a, b, c);
+        let normal = triangle.normal;
+        prop_assume!(normal.magnitude2() > 0.5);
+        prop_assume!(normal.dot(origin - a).abs() > 1.0);
+        let w = 1.0 - u - v;
+        let margin = 1e-3;
+        let inside = u > margin && v > margin && w > margin;
+        let outside = u < -margin || v < -margin || w < -margin;
+        prop_assume!(inside || outside);
+
+        // A ray through a point on the plane of the triangle hits the
+        // triangle if and only if the point is inside it, and so does the
+        // reverse ray through the same point from the other side.
+        let target = a + u * (b - a) + v * (c - a);
+        let direction = (target - origin).normalize();
+        let dist = (target - origin).magnitude();
+        let forward = triangle.collides_with(Ray { origin, direction });
+        let beyond = target + dist * direction;
+        let backward = triangle.collides_with(Ray { origin: beyond, direction: -direction });
+        prop_assert_eq!(forward.is_some(), inside);
+        prop_assert_eq!(backward.is_some(), inside);
+        if inside {
+            let scale = (b - a).magnitude().max((c - a).magnitude());
+            assert_close(forward.unwrap(), dist, scale)?;
+            assert_close(backward.unwrap(), dist, scale)?;
+        }
+    }
+
+    #[test]
+    fn shared_edge_is_watertight(
+        (a, b, c, d) in (arb_point(), arb_point(), arb_point(), arb_point()),
+        origin in arb_point(),
+        t in 0.0f32..=1.0,
+    ) {
+        // A ray through the edge the triangles share hits at least one of
+        // them, as long as it crosses from one to the other.
+        let target = a + t * (b - a);
+        let direction = (target - origin).normalize();
+        let ray = Ray { origin, direction };
+        let side = |p: Vector3<f32>| (b - a).cross(p - a).dot(direction);
+        // Reflecting d through the middle of the edge puts it on the other
+        // side of the edge, as seen along the ray.
+        let d = if side(c) * side(d) > 0.0 { a + b - d } else { d };
+        prop_assume!(side(c) * side(d) < 0.0);
+
+        let first = triangle(a, b, c);
+        let second = triangle(b, a, d);
+        prop_assume!(first.normal.magnitude2() > 0.5 && second.normal.magnitude2() > 0.5);
+        prop_assume!(first.normal.dot(origin - a).abs() > 1.0);
+        prop_assume!(second.normal.dot(origin - a).abs() > 1.0);
+        prop_assert!(first.collides_with(ray).is_some() || second.collides_with(ray).is_some());
+    }
+
+    #[test]
+    fn leaving_rays_miss_their_surface(
+        center in arb_point(),
+        radius in 1e-2f32..100.0,
+        surface in arb_direction(),
+        direction in arb_direction(),
+        scale in prop_oneof![Just(1.0f32), Just(1e3)],
+    ) {
+        let direction = if direction.dot(surface) < 0.0 {
+            -direction
+        } else {
+            direction
+        };
+        prop_assume!(direction.dot(surface) > 1e-2);
+        let center = center * scale;
+        let sphere = Sphere {
+            position: center,
+            radius,
+            material: Material::default(),
+        };
+        let plane = Plane {
+            point: center,
+            normal: surface,
+            material: Material::default(),
+        };
+
+        // Find the point on the surface with a camera ray, as the tracer
+        // does, so that the rounding error is realistic.
+        let origin = center + 3.0 * radius * surface;
+        let ray = Ray { origin, direction: -surface };
+        let pos = ray.project(sphere.collides_with(ray).unwrap());
+        let normal = sphere.normal_at(pos);
+        prop_assert!(sphere.collides_with(Ray::leaving(pos, normal, direction)).is_none());
+
+        let ray = Ray { origin: ray.origin + radius * direction.cross(surface), ..ray };
+        let pos = ray.project(plane.collides_with(ray).unwrap());
+        prop_assert!(plane.collides_with(Ray::leaving(pos, surface, direction)).is_none());
+        prop_assert!(plane.collides_with(Ray::leaving(pos, -surface, direction)).is_none());
+    }
+}
+
+fn triangle(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Triangle {
+    let normal = (b - a).cross(c - a);
+    let normal = if normal.magnitude2() > 0.0 {
+        normal.normalize()
+    } else {
+        normal
+    };
+    Triangle {
+        vertices: (a, b, c),
+        normal,
+        material: Material::default(),
+    }
+}
+
+/// Returns the distance along a ray to the near side of a sphere it hits,
+/// found in double precision.
+fn sphere_distance(sphere: &Sphere, ray: Ray) -> f32 {
+    let oc = to_f64(ray.origin) - to_f64(sphere.position);
+    let d = to_f64(ray.direction);
+    let half_b = d.dot(oc);
+    let c = oc.magnitude2() - f64::from(sphere.radius).powi(2);
+    let a = d.magnitude2();
+    ((-half_b - (half_b * half_b - a * c).max(0.0).sqrt()) / a) as f32
+}
+
+fn to_f64(v: Vector3<f32>) -> Vector3<f64> {
+    Vector3::new(v.x.into(), v.y.into(), v.z.into())
+}
+
+/// Checks that two distances agree to within a small multiple of the rounding
+/// error expected at the given scale.
+fn assert_close(actual: f32, expected: f32, scale: f32) -> Result<(), TestCaseError> {
+    let tolerance = 1e-4 * (scale + expected);
+    prop_assert!(
+        (actual - expected).abs() <= tolerance,
+        "{} isn't within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+    Ok(())
+}
+
+fn arb_point() -> impl Strategy<Value = Vector3<f32>> {
+    (-100.0f32..100.0, -100.0f32..100.0, -100.0f32..100.0)
+        .prop_map(|(x, y, z)| Vector3::new(x, y, z))
+}
+
+fn arb_direction() -> impl Strategy<Value = Vector3<f32>> {
+    arb_point()
+        .prop_filter("zero vector", |v| v.magnitude2() > 1e-2)
+        .prop_map(InnerSpace::normalize)
+}
@@ -1,51 +1,31 @@
-use cgmath::{dot, Vector3};
+use cgmath::{InnerSpace, Vector3};
 
-use material::Material;
+use material::{Material, Sides};
 use ray::Ray;
 use renderable::Renderable;
+use util;
 
 /// A triangle.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Triangle {
     /// The vertices of the triangle.
+    #[serde(with = "vertices")]
     pub vertices: (Vector3<f32>, Vector3<f32>, Vector3<f32>),
 
     /// The normal vector from the triangle.
+    #[serde(with = "util::serde_vector")]
     pub normal: Vector3<f32>,
 
     /// The material the triangle is made of.
     pub material: Material,
 }
 
-impl Triangle {
-    /// Calculates barycentric coordinates on the triangle.
-    fn barycentric(&self, p: Vector3<f32>) -> (f32, f32) {
-        // Current implementation stolen from https://gamedev.stackexchange.com/a/49370
-        let v0 = self.vertices.1 - self.vertices.0;
-        let v1 = self.vertices.2 - self.vertices.0;
-        let v2 = p - self.vertices.0;
-        let d00 = dot(v0, v0);
-        let d01 = dot(v0, v1);
-        let d11 = dot(v1, v1);
-        let d20 = dot(v2, v0);
-        let d21 = dot(v2, v1);
-        let inv_denom = 1.0 / (d00 * d11 - d01 * d01);
-        let alpha = (d11 * d20 - d01 * d21) * inv_denom;
-        let beta = (d00 * d21 - d01 * d20) * inv_denom;
-        (alpha, beta)
-    }
-}
-
 impl Renderable for Triangle {
     fn collides_with(&self, ray: Ray) -> Option<f32> {
-        let dist = ray.collide_plane(self.vertices.0, self.normal)?;
-        let p = ray.project(dist);
-        let (alpha, beta) = self.barycentric(p);
-        if alpha >= 0.0 && beta >= 0.0 && alpha + beta <= 1.0 {
-            Some(dist)
-        } else {
-            None
+        if self.material.sides == Sides::Front && self.normal.dot(ray.direction) >= 0.0 {
+            return None;
         }
+        intersect_triangle(self.vertices, ray)
     }
 
     fn material(&self) -> Material {
@@ -57,3 +37,119 @@ impl Renderable for Triangle {
         self.normal
     }
 }
+
+/// Returns the distance along the ray to where it hits the triangle with the
+/// given vertices, from either side.
+pub(crate) fn intersect_triangle(
+    vertices: (Vector3<f32>, Vector3<f32>, Vector3<f32>),
+    ray: Ray,
+) -> Option<f32> {
+    // The watertight algorithm of Woop, Benthin and Wald, "Watertight
+    // Ray/Triangle Intersection" (JCGT, 2013). The triangle is moved into
+    // a space where the ray starts at the origin and points along the z
+    // axis; a ray through the edge two triangles share then always hits
+    // at least one of them.
+    let Shear {
+        kx,
+        ky,
+        kz,
+        sx,
+        sy,
+        sz,
+    } = Shear::new(ray.direction);
+
+    let (v0, v1, v2) = vertices;
+    let (a, b, c) = (v0 - ray.origin, v1 - ray.origin, v2 - ray.origin);
+    let shear = |v: Vector3<f32>| (v[kx] - sx * v[kz], v[ky] - sy * v[kz]);
+    let ((ax, ay), (bx, by), (cx, cy)) = (shear(a), shear(b), shear(c));
+
+    let mut u = cx * by - cy * bx;
+    let mut v = ax * cy - ay * cx;
+    let mut w = bx * ay - by * ax;
+    if u == 0.0 || v == 0.0 || w == 0.0 {
+        // The ray is on or very near an edge, so the edge functions are
+        // recomputed in double precision to get their signs right.
+        let edge = |px: f32, py: f32, qx: f32, qy: f32| {
+            (f64::from(px) * f64::from(qy) - f64::from(py) * f64::from(qx)) as f32
+        };
+        u = edge(cx, cy, bx, by);
+        v = edge(ax, ay, cx, cy);
+        w = edge(bx, by, ax, ay);
+    }
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz];
+    let dist = t / det;
+    if dist >= 0.0 {
+        Some(dist)
+    } else {
+        None
+    }
+}
+
+/// The parts of the watertight intersection test that only depend on the
+/// ray's direction: the axis it's most along, `kz`, the other two, and the
+/// factors that shear and scale it onto the z axis.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Shear {
+    pub kx: usize,
+    pub ky: usize,
+    pub kz: usize,
+    pub sx: f32,
+    pub sy: f32,
+    pub sz: f32,
+}
+
+impl Shear {
+    pub fn new(dir: Vector3<f32>) -> Shear {
+        let kz = if dir.x.abs() > dir.y.abs() {
+            if dir.x.abs() > dir.z.abs() {
+                0
+            } else {
+                2
+            }
+        } else if dir.y.abs() > dir.z.abs() {
+            1
+        } else {
+            2
+        };
+        let (kx, ky) = if dir[kz] < 0.0 {
+            ((kz + 2) % 3, (kz + 1) % 3)
+        } else {
+            ((kz + 1) % 3, (kz + 2) % 3)
+        };
+        Shear {
+            kx,
+            ky,
+            kz,
+            sx: dir[kx] / dir[kz],
+            sy: dir[ky] / dir[kz],
+            sz: dir[kz].recip(),
+        }
+    }
+}
+
+/// Serializes the vertices of a triangle as `[[x, y, z], [x, y, z], [x, y, z]]`.
+mod vertices {
+    use cgmath::Vector3;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    type Vertices = (Vector3<f32>, Vector3<f32>, Vector3<f32>);
+
+    pub fn serialize<S: Serializer>(v: &Vertices, s: S) -> Result<S::Ok, S::Error> {
+        let (a, b, c) = *v;
+        let vertices: [[f32; 3]; 3] = [a.into(), b.into(), c.into()];
+        vertices.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vertices, D::Error> {
+        let [a, b, c] = <[[f32; 3]; 3]>::deserialize(d)?;
+        Ok((a.into(), b.into(), c.into()))
+    }
+}
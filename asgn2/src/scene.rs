@@ -1,27 +1,43 @@
-use std::mem::forget;
+use std::ops::Range;
 use std::path::PathBuf;
+use std::time::Instant;
 
 use cgmath::Vector3;
-use image::{Pixel, Rgb, RgbImage};
+use image::{Pixel, Rgb};
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
 
+use framebuffer::Framebuffer;
 use light::DynamicLight;
+use material::Material;
+use mesh::Mesh;
 use renderable::DynamicRenderable;
+use stats::{Counters, RenderStats};
+use trace::{emitters, LANES};
+use util;
 
 /// A single renderable scene.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// Scenes can also be serialized, for the structured scene formats. There,
+/// the camera's half-angle is given in degrees as `camera_half_angle`, and
+/// `max_collisions` is called `max_depth`, as in the scene file format.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Scene {
     /// The position the camera is at.
+    #[serde(with = "util::serde_vector")]
     pub camera_position: Vector3<f32>,
 
     /// The direction the camera is facing. Normalized.
+    #[serde(with = "util::serde_vector")]
     pub camera_direction: Vector3<f32>,
 
     /// The "up vector" for the camera. Normalized.
+    #[serde(with = "util::serde_vector")]
     pub camera_up: Vector3<f32>,
 
     /// The tangent of one half of the vertical angle of the view frustrum.
+    #[serde(rename = "camera_half_angle", with = "half_angle")]
     pub camera_half_angle_tan: f32,
 
     /// The width of the output image.
@@ -31,54 +47,148 @@ pub struct Scene {
     pub height: u32,
 
     /// The file to save the output image to.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub output_image: Option<PathBuf>,
 
     /// The ambient light color.
+    #[serde(with = "util::serde_rgb")]
     pub ambient_light: Rgb<f32>,
 
     /// The background light color.
+    #[serde(with = "util::serde_rgb")]
     pub background: Rgb<f32>,
 
+    /// Whether rays that hit nothing should be transparent rather than
+    /// `background`, so the image can be composited over another one.
+    pub transparent_background: bool,
+
     /// The lights in the scene.
     pub lights: Vec<DynamicLight>,
 
     /// The objects in the scene.
     pub objects: Vec<DynamicRenderable>,
 
+    /// The triangle meshes in the scene. They're numbered after the objects,
+    /// so where the mesh at index `i` here is hit, it's object
+    /// `objects.len() + i`. In scene files, each `mesh` directive adds a
+    /// mesh, and so does each run of `triangle` or `normal_triangle`
+    /// directives, rather than adding an object per triangle.
+    pub meshes: Vec<Mesh>,
+
+    /// The materials of the meshes, which refer to them by their index here.
+    pub materials: Vec<Material>,
+
     /// The maximum number of collisions to process.
     ///
     /// TODO: What happens when this is triggered.
+    #[serde(rename = "max_depth")]
     pub max_collisions: usize,
+
+    /// The number of camera rays traced per pixel, which are spread over the
+    /// pixel and averaged to antialias the image. 0 is treated as 1.
+    pub samples: usize,
+
+    /// The number of points sampled on the surface of each emissive sphere,
+    /// triangle or mesh when lighting a point, to find how much of it is in
+    /// shadow. A mesh's points are spread over all of its triangles. 0 is
+    /// treated as 1.
+    pub light_samples: usize,
 }
 
 impl Scene {
     /// Renders the scene to an image.
-    pub fn render(&self) -> RgbImage {
-        let rays = self.render_rays();
-        let buf = unsafe {
-            let len = rays.len();
-            let cap = rays.capacity();
-            let ptr = rays.as_ptr();
-            forget(rays);
-            Vec::from_raw_parts(ptr as *mut u8, len * 3, cap * 3)
-        };
-        RgbImage::from_raw(self.width, self.height, buf).unwrap()
+    pub fn render(&self) -> Framebuffer {
+        self.render_with_stats().0
     }
 
+    /// Renders the scene to an image, collecting statistics about the render.
+    pub fn render_with_stats(&self) -> (Framebuffer, RenderStats) {
+        let start = Instant::now();
+        let stats = Counters::default();
+        let pixels = self.render_region(0..self.width, 0..self.height, &stats);
+        let image = Framebuffer::new(self.width, self.height, pixels);
+        (image, stats.finish(start.elapsed()))
+    }
+
+    /// Renders the scene to an image, tracing each camera ray on its own
+    /// rather than in packets. The image is identical to `render`'s, but takes
+    /// longer to render; this is for testing and benchmarking packets, and
+    /// only exists in tests or with the `scalar` feature.
+    #[cfg(any(test, feature = "scalar"))]
+    #[doc(hidden)]
+    pub fn render_scalar(&self) -> Framebuffer {
+        let stats = Counters::default();
+        let samples = self.samples.max(1);
+        let emitters = emitters(self);
+        let pixels = self.render_pixels(0..self.width, 0..self.height, |x, y| {
+            self.trace_pixel(x, y, samples, &emitters, &stats)
+        });
+        Framebuffer::new(self.width, self.height, pixels)
+    }
+
+    /// Returns the material of the object or mesh with the given index, as
+    /// returned by `object_at`, to change it. A mesh sharing its material with
+    /// others is given its own copy first, so the others are left alone.
+    pub fn material_mut(&mut self, i: usize) -> Option<&mut Material> {
+        if i < self.objects.len() {
+            return Some(self.objects[i].material_mut());
+        }
+        let j = i - self.objects.len();
+        let material = self.meshes.get(j)?.material;
+        if self
+            .meshes
+            .iter()
+            .filter(|m| m.material == material)
+            .count()
+            > 1
+        {
+            self.materials.push(self.materials[material]);
+            self.meshes[j].material = self.materials.len() - 1;
+        }
+        Some(&mut self.materials[self.meshes[j].material])
+    }
+
+    /// Renders the pixels in the given columns and rows, returning them in
+    /// row-major order. The camera rays of runs of `LANES` pixels in each row
+    /// are traced together, in packets.
+    pub(crate) fn render_region(
+        &self,
+        xs: Range<u32>,
+        ys: Range<u32>,
+        stats: &Counters,
+    ) -> Vec<[f32; 4]> {
+        let samples = self.samples.max(1);
+        let emitters = emitters(self);
+        let lanes = LANES as u32;
+        let runs = xs.end.saturating_sub(xs.start).div_ceil(lanes);
+        let pixels = self.render_pixels(0..runs, ys, |run, y| {
+            let start = xs.start + run * lanes;
+            let xs = start..(start + lanes).min(xs.end);
+            self.trace_pixels(xs, y, samples, &emitters, stats)
+        });
+        pixels.into_iter().flatten().collect()
+    }
+
+    /// Calls the given function for each pixel in the given columns and rows,
+    /// collecting the results in row-major order.
     #[cfg(feature = "rayon")]
-    fn render_rays(&self) -> Vec<[u8; 3]> {
-        (0..self.height)
-            .into_par_iter()
-            .flat_map(|y| (0..self.width).into_par_iter().map(move |x| (x, y)))
-            .map(|(x, y)| self.trace_pixel(x, y))
+    pub(crate) fn render_pixels<T, F>(&self, xs: Range<u32>, ys: Range<u32>, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(u32, u32) -> T + Sync,
+    {
+        ys.into_par_iter()
+            .flat_map(|y| xs.clone().into_par_iter().map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
             .collect()
     }
     #[cfg(not(feature = "rayon"))]
-    fn render_rays(&self) -> Vec<[u8; 3]> {
-        (0..self.height)
-            .into_iter()
-            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
-            .map(|(x, y)| self.trace_pixel(x, y))
+    pub(crate) fn render_pixels<T, F>(&self, xs: Range<u32>, ys: Range<u32>, f: F) -> Vec<T>
+    where
+        F: Fn(u32, u32) -> T,
+    {
+        ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
             .collect()
     }
 }
@@ -95,9 +205,27 @@ impl Default for Scene {
             output_image: None,
             ambient_light: Pixel::from_channels(0.0, 0.0, 0.0, 1.0),
             background: Pixel::from_channels(0.0, 0.0, 0.0, 1.0),
+            transparent_background: false,
             lights: Vec::new(),
             objects: Vec::new(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
             max_collisions: 5,
+            samples: 1,
+            light_samples: 16,
         }
     }
 }
+
+/// Serializes the tangent of the camera's half-angle as the angle in degrees.
+mod half_angle {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(tan: &f32, s: S) -> Result<S::Ok, S::Error> {
+        tan.atan().to_degrees().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<f32, D::Error> {
+        f32::deserialize(d).map(|a| a.to_radians().tan())
+    }
+}
@@ -0,0 +1,398 @@
+//! A render server, which keeps parsed scenes in memory between renders.
+//!
+//! Clients connect to a Unix socket and write a `RenderRequest` as a single
+//! line of JSON. The server replies with a line of JSON, either
+//! `{"image": {"length": N}}` followed by N bytes of PNG, or
+//! `{"error": {"message": "..."}}`, and closes the connection.
+//!
+//! Scenes are identified by the path of their file, as seen by the server,
+//! and must be inside its scene root. A scene is parsed the first time it's
+//! requested, and again only if the file's modification time changes; files
+//! it includes aren't checked.
+//!
+//! The server's `Limits` bound the images it renders and the connections it
+//! handles at once, so that a client can't exhaust it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use image::png::PNGEncoder;
+use image::ColorType;
+use serde::Serialize;
+use serde_json;
+
+use animation::{Animation, CameraKey, Track};
+use scene::Scene;
+use validate::Severity;
+
+/// A request to render a scene.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RenderRequest {
+    /// The path of the scene file.
+    pub scene: PathBuf,
+
+    /// Overrides for the camera. The `frame` of the keyframe is ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraKey>,
+
+    /// Overrides the width of the image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+
+    /// Overrides the height of the image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+
+    /// Overrides the number of samples per pixel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<usize>,
+}
+
+/// The header of the server's reply to a request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    /// The render succeeded, and the given number of bytes of PNG follow.
+    Image { length: usize },
+
+    /// The render failed.
+    Error { message: String },
+}
+
+/// The longest a request line can be, in bytes.
+const MAX_REQUEST: u64 = 1 << 16;
+
+/// How long the server waits for a client to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Limits on what a render server will do.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    /// The directory requested scenes must be in. Relative paths in requests
+    /// are relative to it.
+    pub root: PathBuf,
+
+    /// The most pixels a rendered image can have.
+    pub max_pixels: u64,
+
+    /// The most samples per pixel a render can take.
+    pub max_samples: usize,
+
+    /// The number of connections handled at once. Others wait until one of
+    /// them is finished.
+    pub threads: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            root: PathBuf::from("."),
+            max_pixels: 4096 * 4096,
+            max_samples: 64,
+            threads: 4,
+        }
+    }
+}
+
+/// A scene that has been parsed.
+struct Cached {
+    /// The modification time of the file when it was parsed.
+    modified: Option<SystemTime>,
+
+    scene: Arc<Scene>,
+}
+
+/// A render server.
+#[derive(Default)]
+pub struct Server {
+    limits: Limits,
+
+    /// The scenes that have been parsed, by their canonical path.
+    scenes: Mutex<HashMap<PathBuf, Cached>>,
+}
+
+impl Server {
+    /// Creates a server with no scenes loaded and the default limits.
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    /// Creates a server with no scenes loaded and the given limits.
+    pub fn with_limits(limits: Limits) -> Server {
+        Server {
+            limits,
+            ..Server::default()
+        }
+    }
+
+    /// Returns a scene, parsing it if it hasn't been loaded or has changed.
+    /// Unlike a request's, the path needn't be in the scene root.
+    ///
+    /// Other scenes can be loaded while this one is parsed, so a scene
+    /// requested twice at once may be parsed twice.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Arc<Scene>, String> {
+        let path = path.as_ref();
+        let path = path
+            .canonicalize()
+            .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+        if let Some(cached) = self.scenes().get(&path) {
+            if modified.is_some() && cached.modified == modified {
+                return Ok(cached.scene.clone());
+            }
+        }
+        // The source lines in parse errors are left out, since they'd show
+        // clients the contents of files.
+        let scene = Scene::from_file(&path).map_err(|errs| {
+            let messages = errs.0.iter().map(|err| err.message()).collect::<Vec<_>>();
+            messages.join("\n")
+        })?;
+        let scene = Arc::new(scene);
+        self.scenes().insert(
+            path,
+            Cached {
+                modified,
+                scene: scene.clone(),
+            },
+        );
+        Ok(scene)
+    }
+
+    /// Locks the scenes that have been parsed.
+    fn scenes(&self) -> MutexGuard<'_, HashMap<PathBuf, Cached>> {
+        self.scenes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Finds the file a request's scene path refers to, which must be in the
+    /// scene root.
+    fn resolve(&self, path: &Path) -> Result<PathBuf, String> {
+        let root = self.limits.root.canonicalize().map_err(|err| {
+            format!(
+                "couldn't read the scene root {}: {}",
+                self.limits.root.display(),
+                err
+            )
+        })?;
+        let resolved = root
+            .join(path)
+            .canonicalize()
+            .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+        if resolved.starts_with(&root) {
+            Ok(resolved)
+        } else {
+            Err(format!("{} is outside the scene root", path.display()))
+        }
+    }
+
+    /// Renders a scene as requested, returning the image as a PNG.
+    pub fn render(&self, request: &RenderRequest) -> Result<Vec<u8>, String> {
+        let scene = self.load(self.resolve(&request.scene)?)?;
+        let mut scene = match request.camera {
+            Some(ref camera) => {
+                let animation = Animation {
+                    camera: Track {
+                        keyframes: vec![camera.clone()],
+                        ..Track::default()
+                    },
+                    ..Animation::default()
+                };
+                animation.apply(&scene, 0.0)
+            }
+            None => (*scene).clone(),
+        };
+        if let Some(width) = request.width {
+            scene.width = width;
+        }
+        if let Some(height) = request.height {
+            scene.height = height;
+        }
+        if let Some(samples) = request.samples {
+            scene.samples = samples;
+        }
+        let pixels = u64::from(scene.width) * u64::from(scene.height);
+        if pixels > self.limits.max_pixels {
+            return Err(format!(
+                "the image has {} pixels, more than the limit of {}",
+                pixels, self.limits.max_pixels
+            ));
+        }
+        if scene.samples > self.limits.max_samples {
+            return Err(format!(
+                "{} samples per pixel is more than the limit of {}",
+                scene.samples, self.limits.max_samples
+            ));
+        }
+
+        let errors = scene
+            .validate()
+            .into_iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+            .map(|issue| issue.to_string())
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        encode_png(&scene).map_err(|err| err.to_string())
+    }
+
+    /// Serves requests from a socket until it fails, handling connections on
+    /// a fixed number of threads.
+    pub fn serve(&self, listener: &UnixListener) -> IoResult<()> {
+        thread::scope(|s| {
+            let threads = (0..self.limits.threads.max(1))
+                .map(|_| {
+                    s.spawn(|| -> IoResult<()> {
+                        for stream in listener.incoming() {
+                            if let Err(err) = self.handle(stream?) {
+                                error!("{}", err);
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            for t in threads {
+                t.join()
+                    .unwrap_or_else(|_| Err(IoError::other("server thread panicked")))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads a request from a connection and replies to it.
+    fn handle(&self, stream: UnixStream) -> IoResult<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut line = String::new();
+        BufReader::new((&stream).take(MAX_REQUEST)).read_line(&mut line)?;
+        let result = serde_json::from_str(&line)
+            .map_err(|err| format!("invalid request: {}", err))
+            .and_then(|request| self.render(&request));
+
+        let mut stream = stream;
+        match result {
+            Ok(png) => {
+                write_line(&mut stream, &Response::Image { length: png.len() })?;
+                stream.write_all(&png)?;
+            }
+            Err(message) => write_line(&mut stream, &Response::Error { message })?,
+        }
+        stream.flush()
+    }
+}
+
+/// Sends a render request to the server listening on a socket, returning the
+/// image as a PNG.
+pub fn request<P: AsRef<Path>>(socket: P, request: &RenderRequest) -> IoResult<Vec<u8>> {
+    let mut stream = UnixStream::connect(socket)?;
+    write_line(&mut stream, request)?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let response =
+        serde_json::from_str(&line).map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+    match response {
+        Response::Image { length } => {
+            let mut png = vec![0; length];
+            reader.read_exact(&mut png)?;
+            Ok(png)
+        }
+        Response::Error { message } => Err(IoError::other(message)),
+    }
+}
+
+/// Writes a value as a line of JSON.
+fn write_line<T: Serialize>(stream: &mut UnixStream, value: &T) -> IoResult<()> {
+    let mut json = serde_json::to_vec(value)?;
+    json.push(b'\n');
+    stream.write_all(&json)
+}
+
+/// Renders a scene to a PNG, with an alpha channel if its background is
+/// transparent.
+fn encode_png(scene: &Scene) -> IoResult<Vec<u8>> {
+    let image = scene.render();
+    let (width, height) = (image.width(), image.height());
+    let mut png = Vec::new();
+    if scene.transparent_background {
+        PNGEncoder::new(&mut png).encode(&image.to_rgba8(), width, height, ColorType::RGBA(8))?;
+    } else {
+        PNGEncoder::new(&mut png).encode(&image.to_rgb8(), width, height, ColorType::RGB(8))?;
+    }
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::process;
+    use std::thread;
+
+    use server::{request, Limits, RenderRequest, Server};
+
+    #[test]
+    fn serve() {
+        let dir = env::temp_dir().join(format!("raytracer-server-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let scene = dir.join("scene.scn");
+        fs::write(&scene, "film_resolution 4 3\nsphere 0 0 2 1\n").unwrap();
+        let socket = dir.join("socket");
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let limits = Limits {
+            root: dir.clone(),
+            max_pixels: 100,
+            ..Limits::default()
+        };
+        thread::spawn(move || Server::with_limits(limits).serve(&listener));
+
+        let mut req = RenderRequest {
+            scene: scene.clone(),
+            width: Some(2),
+            ..RenderRequest::default()
+        };
+        let png = request(&socket, &req).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        // The width and height are big-endian in the IHDR chunk.
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 3]);
+
+        // Paths are relative to the scene root, and can't leave it.
+        req.scene = "scene.scn".into();
+        assert!(request(&socket, &req).is_ok());
+        req.scene = dir.join("../..");
+        let err = request(&socket, &req).unwrap_err();
+        assert!(
+            err.to_string().contains("outside the scene root"),
+            "{}",
+            err
+        );
+
+        req.scene = dir.join("missing.scn");
+        let err = request(&socket, &req).unwrap_err();
+        assert!(err.to_string().contains("missing.scn"), "{}", err);
+
+        // Parse errors don't include the source lines.
+        let bad = dir.join("bad.scn");
+        fs::write(&bad, "secret 1 2 3\n").unwrap();
+        req.scene = bad;
+        let err = request(&socket, &req).unwrap_err().to_string();
+        assert!(err.contains("line 1, column 1"), "{}", err);
+        assert!(!err.contains("1 2 3"), "{}", err);
+
+        req.scene = scene.clone();
+        req.width = Some(40);
+        let err = request(&socket, &req).unwrap_err();
+        assert!(err.to_string().contains("limit of 100"), "{}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
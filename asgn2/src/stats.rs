@@ -0,0 +1,64 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use util::millis;
+
+/// Statistics collected while rendering a scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    /// The number of rays cast from the camera.
+    pub camera_rays: usize,
+
+    /// The number of rays cast towards lights to check for shadows, counting
+    /// each stretch between the transmissive surfaces they pass through.
+    pub shadow_rays: usize,
+
+    /// The number of rays refracted through transmissive surfaces. Materials
+    /// don't reflect, so there are no reflection rays.
+    pub secondary_rays: usize,
+
+    /// The number of ray-object intersection tests performed.
+    pub intersection_tests: usize,
+
+    /// The time taken to render the scene.
+    pub render_time: Duration,
+}
+
+impl Display for RenderStats {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        writeln!(fmt, "camera rays:        {}", self.camera_rays)?;
+        writeln!(fmt, "shadow rays:        {}", self.shadow_rays)?;
+        writeln!(fmt, "secondary rays:     {}", self.secondary_rays)?;
+        writeln!(fmt, "intersection tests: {}", self.intersection_tests)?;
+        write!(fmt, "render time:        {}ms", millis(self.render_time))
+    }
+}
+
+/// The counters that are incremented while rendering. These are atomic, since
+/// pixels may be rendered in parallel.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    pub camera_rays: AtomicUsize,
+    pub shadow_rays: AtomicUsize,
+    pub secondary_rays: AtomicUsize,
+    pub intersection_tests: AtomicUsize,
+}
+
+impl Counters {
+    /// Increments a counter by the given amount.
+    pub fn add(counter: &AtomicUsize, n: usize) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Converts the counters into a `RenderStats`.
+    pub fn finish(self, render_time: Duration) -> RenderStats {
+        RenderStats {
+            camera_rays: self.camera_rays.into_inner(),
+            shadow_rays: self.shadow_rays.into_inner(),
+            secondary_rays: self.secondary_rays.into_inner(),
+            intersection_tests: self.intersection_tests.into_inner(),
+            render_time,
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+
+use light::{Attenuation, Point};
+use material::{Material, Sides};
+use ray::Ray;
+use renderable::{DynamicRenderable, Renderable, Triangle};
+use scene::Scene;
+use trace::spread;
+use util::BLACK;
+
+/// Something in the scene that lights it: an emissive object or mesh.
+#[derive(Clone, Debug)]
+pub(crate) enum Emitter {
+    /// An emissive sphere or triangle.
+    Object(DynamicRenderable),
+
+    /// An emissive mesh, as its triangles, which are sampled together.
+    Mesh {
+        /// The triangles of the mesh.
+        triangles: Vec<Triangle>,
+
+        /// The running total of the triangles' areas, so that a triangle can
+        /// be picked with a chance proportional to its area.
+        areas: Vec<f32>,
+    },
+}
+
+impl Emitter {
+    /// Returns the material of the emitter.
+    fn material(&self) -> Material {
+        match *self {
+            Emitter::Object(ref obj) => obj.material(),
+            Emitter::Mesh { ref triangles, .. } => triangles[0].material,
+        }
+    }
+}
+
+/// Returns the objects and meshes in the scene that light it: the emissive
+/// ones, other than planes and meshes with no area. This is found once per
+/// render, rather than for every point lit.
+pub(crate) fn emitters(scene: &Scene) -> Vec<Emitter> {
+    let objects = scene.objects.iter().filter(|obj| match **obj {
+        DynamicRenderable::Plane(_) => false,
+        ref obj => obj.material().emissive != BLACK,
+    });
+    let meshes = scene
+        .meshes
+        .iter()
+        .filter(|m| scene.materials[m.material].emissive != BLACK)
+        .filter_map(|m| {
+            let triangles = m.triangles(scene.materials[m.material]);
+            let areas = triangles
+                .iter()
+                .scan(0.0, |total, t| {
+                    *total += area(t);
+                    Some(*total)
+                })
+                .collect::<Vec<_>>();
+            match areas.last() {
+                Some(&total) if total > 0.0 => Some(Emitter::Mesh { triangles, areas }),
+                _ => None,
+            }
+        });
+    objects
+        .cloned()
+        .map(Emitter::Object)
+        .chain(meshes)
+        .collect()
+}
+
+/// Returns point lights standing in for the light an emitter gives off
+/// towards a point, one at each of `samples` points spread over the part of
+/// its surface that can face the point. Each gives off the light of its share
+/// of that area, so casting shadow rays towards them finds how much of the
+/// surface is hidden from the point. A mesh's samples are spread over all of
+/// its triangles, so it costs no more to sample than a single triangle.
+///
+/// Emitters that don't give off light have no samples, and neither do
+/// planes, since they're infinite.
+pub(crate) fn samples(emitter: &Emitter, pos: Vector3<f32>, samples: usize) -> Vec<Point> {
+    let material = emitter.material();
+    if material.emissive == BLACK {
+        return Vec::new();
+    }
+    let samples = samples.max(1);
+    let mut lights = Vec::with_capacity(samples);
+    for i in 0..samples {
+        let (u, v) = spread(i, samples);
+        let (point, normal, area) = match *emitter {
+            Emitter::Object(DynamicRenderable::Triangle(ref t)) => {
+                let (point, normal) = point_on(t, u, v);
+                (point, normal, area(t))
+            }
+            Emitter::Mesh {
+                ref triangles,
+                ref areas,
+            } => {
+                // The triangle is picked by where `u` falls among the
+                // running total of the areas, and `u` is then rescaled to
+                // pick a point on it.
+                let total = areas[areas.len() - 1];
+                let target = u * total;
+                let j = areas
+                    .iter()
+                    .position(|&a| a > target)
+                    .unwrap_or(areas.len() - 1);
+                let start = if j > 0 { areas[j - 1] } else { 0.0 };
+                let u = ((target - start) / (areas[j] - start)).clamp(0.0, 1.0);
+                let (point, normal) = point_on(&triangles[j], u, v);
+                (point, normal, total)
+            }
+            Emitter::Object(DynamicRenderable::Sphere(ref s)) => {
+                // From outside the sphere, only the hemisphere facing the point
+                // can be seen, so only it is sampled. From inside, all of the
+                // sphere can be.
+                let towards = pos - s.position;
+                let inside = towards.magnitude2() < s.radius * s.radius;
+                let (z, hemispheres) = if inside {
+                    (1.0 - 2.0 * u, 2.0)
+                } else {
+                    (u, 1.0)
+                };
+                let (w, x, y) = basis(towards);
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * v;
+                let normal = z * w + r * (phi.cos() * x + phi.sin() * y);
+                let area = hemispheres * 2.0 * PI * s.radius * s.radius;
+                (s.position + s.radius * normal, normal, area)
+            }
+            Emitter::Object(DynamicRenderable::Plane(_)) => return Vec::new(),
+        };
+
+        let to_pos = pos - point;
+        let cos = normal.dot(to_pos) / to_pos.magnitude();
+        let cos = if material.sides == Sides::Both {
+            cos.abs()
+        } else {
+            cos
+        };
+        if cos > 0.0 {
+            lights.push(Point {
+                color: material.emissive,
+                position: Ray::leaving(point, normal, to_pos).origin,
+                intensity: material.emissive_intensity * area * cos / samples as f32,
+                attenuation: Attenuation::INVERSE_SQUARE,
+            });
+        }
+    }
+    lights
+}
+
+/// Returns the area of a triangle.
+fn area(t: &Triangle) -> f32 {
+    let (a, b, c) = t.vertices;
+    0.5 * (b - a).cross(c - a).magnitude()
+}
+
+/// Returns the point on a triangle for a point in the unit square, and the
+/// triangle's normal. Points spread evenly over the square are spread evenly
+/// over the triangle, by the method of Osada et al., "Shape Distributions".
+fn point_on(t: &Triangle, u: f32, v: f32) -> (Vector3<f32>, Vector3<f32>) {
+    let (a, b, c) = t.vertices;
+    let su = u.sqrt();
+    let point = (1.0 - su) * a + su * (1.0 - v) * b + su * v * c;
+    (point, t.normal.normalize())
+}
+
+/// Returns an orthonormal basis whose first vector points along the given
+/// one, or along the z axis if it's zero.
+fn basis(v: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+    let w = if v.magnitude2() > 0.0 {
+        v.normalize()
+    } else {
+        Vector3::unit_z()
+    };
+    let other = if w.x.abs() > 0.5 {
+        Vector3::unit_y()
+    } else {
+        Vector3::unit_x()
+    };
+    let x = w.cross(other).normalize();
+    (w, x, w.cross(x))
+}
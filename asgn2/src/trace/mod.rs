@@ -1,95 +1,696 @@
+mod emitter;
+mod packet;
 mod to_ray;
 
 use std::f32;
+use std::ops::Range;
 
 use cgmath::{Angle, InnerSpace, Vector3};
 use float_ord::FloatOrd;
 use image::Rgb;
 
 use light::Light;
+use material::Material;
 use ray::Ray;
-use renderable::{DynamicRenderable, Renderable};
+use renderable::{DynamicRenderable, Renderable, Triangle};
 use scene::Scene;
-use util::{add_colors, mul_colors, scale_color, BLACK};
+use stats::Counters;
+pub(crate) use trace::emitter::{emitters, Emitter};
+use trace::packet::Packet;
+pub(crate) use trace::packet::LANES;
+use util::{
+    add_colors, clamp_color, mul_colors, rgb_from_vector, scale_color, vector_from_rgb, BLACK,
+    WHITE,
+};
 
 const MIN_INTENSITY: f32 = 0.0001;
 
 impl Scene {
-    pub(crate) fn trace_pixel(&self, x: u32, y: u32) -> [u8; 3] {
-        fn f32_to_u8(x: f32) -> u8 {
-            debug_assert!(x <= 1.0);
-            debug_assert!(x >= 0.0);
-            (x * 255.0) as u8
+    /// Traces the camera rays for a pixel, returning its color and coverage.
+    /// A single ray goes through the pixel's corner; more are spread over the
+    /// pixel and averaged. `emitters` are the scene's `emitters`.
+    #[cfg(any(test, feature = "scalar"))]
+    pub(crate) fn trace_pixel(
+        &self,
+        x: u32,
+        y: u32,
+        samples: usize,
+        emitters: &[Emitter],
+        stats: &Counters,
+    ) -> [f32; 4] {
+        let mut color = Vector3::new(0.0, 0.0, 0.0);
+        let mut coverage = 0.0;
+        for i in 0..samples {
+            let (dx, dy) = sample_offset(i, samples);
+            Counters::add(&stats.camera_rays, 1);
+            let ray = self.make_camera_ray(x as f32 + dx, y as f32 + dy);
+            let (px, alpha) = self.trace_ray(ray, self.max_collisions, emitters, stats);
+            color += vector_from_rgb(px);
+            coverage += alpha;
+        }
+        self.finish_pixel(color, coverage, samples)
+    }
+
+    /// Traces the camera rays for a run of pixels in a row, as `trace_pixel`
+    /// does, but finding what they hit in packets. Each packet holds the same
+    /// sample of neighbouring pixels.
+    pub(crate) fn trace_pixels(
+        &self,
+        xs: Range<u32>,
+        y: u32,
+        samples: usize,
+        emitters: &[Emitter],
+        stats: &Counters,
+    ) -> Vec<[f32; 4]> {
+        let pixels = xs.len();
+        let mut rays = Vec::with_capacity(pixels * samples);
+        for i in 0..samples {
+            let (dx, dy) = sample_offset(i, samples);
+            for x in xs.clone() {
+                rays.push(self.make_camera_ray(x as f32 + dx, y as f32 + dy));
+            }
+        }
+        Counters::add(&stats.camera_rays, rays.len());
+
+        let mut seen = Vec::with_capacity(rays.len());
+        for rays in rays.chunks(LANES) {
+            let collisions = self.closest_collisions(&Packet::new(rays), stats);
+            for (&ray, &collision) in rays.iter().zip(&collisions) {
+                let hit = collision.map(|(i, face, dist)| (self.hit(i, face), dist));
+                seen.push(self.trace_hit(ray, hit, self.max_collisions, emitters, stats));
+            }
         }
-        let px = self
-            .trace_ray(self.make_camera_ray(x, y), self.max_collisions)
-            .data;
-        [f32_to_u8(px[0]), f32_to_u8(px[1]), f32_to_u8(px[2])]
+
+        (0..pixels)
+            .map(|p| {
+                let mut color = Vector3::new(0.0, 0.0, 0.0);
+                let mut coverage = 0.0;
+                for i in 0..samples {
+                    let (px, alpha) = seen[i * pixels + p];
+                    color += vector_from_rgb(px);
+                    coverage += alpha;
+                }
+                self.finish_pixel(color, coverage, samples)
+            })
+            .collect()
+    }
+
+    /// Averages the colors and coverages a pixel's samples saw, giving its
+    /// final color and coverage.
+    fn finish_pixel(&self, color: Vector3<f32>, coverage: f32, samples: usize) -> [f32; 4] {
+        let n = samples as f32;
+        let (px, alpha) = (rgb_from_vector(color / n), coverage / n);
+        let px = if self.transparent_background && alpha > 0.0 {
+            // Un-premultiply the color, since misses contributed black to it.
+            clamp_color(scale_color(px, alpha.recip()))
+        } else {
+            px
+        };
+        [px.data[0], px.data[1], px.data[2], alpha]
     }
 
-    fn trace_ray(&self, ray: Ray, remaining_collisions: usize) -> Rgb<f32> {
-        if let Some((obj, dist)) = self.closest_collision(ray) {
-            let amb = mul_colors(obj.material().ambient, self.ambient_light);
+    /// Traces a ray, returning the color it sees and its coverage. The
+    /// coverage is 0 if the ray hit nothing and 1 if it hit an opaque object;
+    /// transmissive objects let some of the coverage of what's behind them
+    /// through.
+    fn trace_ray(
+        &self,
+        ray: Ray,
+        remaining_collisions: usize,
+        emitters: &[Emitter],
+        stats: &Counters,
+    ) -> (Rgb<f32>, f32) {
+        let hit = self
+            .closest_collision(ray, stats)
+            .map(|(_, obj, dist)| (obj, dist));
+        self.trace_hit(ray, hit, remaining_collisions, emitters, stats)
+    }
+
+    /// Finishes tracing a ray, given what it hit and how far away, if
+    /// anything.
+    fn trace_hit(
+        &self,
+        ray: Ray,
+        hit: Option<(Hit, f32)>,
+        remaining_collisions: usize,
+        emitters: &[Emitter],
+        stats: &Counters,
+    ) -> (Rgb<f32>, f32) {
+        if let Some((obj, dist)) = hit {
+            let pos = ray.project(dist);
+            let geometric = obj.normal_at(pos).normalize();
+            let front = geometric.dot(ray.direction) <= 0.0;
+            let norm = if front { geometric } else { -geometric };
+            let material = obj.material().side(front);
+            let amb = mul_colors(material.ambient, self.ambient_light);
             // TODO: This is not actually diffuse; this is just light bouncing off it...
-            let dif = mul_colors(
-                obj.material().diffuse,
-                self.light_on(obj, ray.project(dist)),
-            );
-            add_colors(amb, dif)
+            let dif = mul_colors(material.diffuse, self.light_on(pos, norm, emitters, stats));
+            let emitted = scale_color(material.emissive, material.emissive_intensity);
+            let local = add_colors(add_colors(amb, dif), emitted);
+
+            let t = material.transmissive;
+            if t == BLACK || remaining_collisions == 0 {
+                return (local, 1.0);
+            }
+            let dir = match refract(ray.direction.normalize(), geometric, material.ior) {
+                Some(dir) => dir,
+                None => return (local, 1.0),
+            };
+
+            Counters::add(&stats.secondary_rays, 1);
+            let ray = Ray::leaving(pos, geometric, dir);
+            let (behind, behind_alpha) =
+                self.trace_ray(ray, remaining_collisions - 1, emitters, stats);
+            let opacity = Rgb {
+                data: [1.0 - t.data[0], 1.0 - t.data[1], 1.0 - t.data[2]],
+            };
+            let t_avg = (t.data[0] + t.data[1] + t.data[2]) / 3.0;
+            let color = add_colors(mul_colors(local, opacity), mul_colors(behind, t));
+            (color, 1.0 - t_avg * (1.0 - behind_alpha))
+        } else if self.transparent_background {
+            (BLACK, 0.0)
         } else {
-            self.background
+            (self.background, 0.0)
         }
     }
 
-    fn closest_collision(&self, ray: Ray) -> Option<(&DynamicRenderable, f32)> {
-        self.objects
+    /// Finds the closest object or mesh the ray collides with, returning its
+    /// index, what it hit, and the distance to the collision. Meshes are
+    /// numbered after the objects.
+    pub(crate) fn closest_collision(
+        &self,
+        ray: Ray,
+        stats: &Counters,
+    ) -> Option<(usize, Hit<'_>, f32)> {
+        let faces = self.meshes.iter().map(|m| m.faces.len()).sum::<usize>();
+        Counters::add(&stats.intersection_tests, self.objects.len() + faces);
+        let objects = self
+            .objects
             .iter()
-            .filter_map(|o| o.collides_with(ray).map(|d| (o, d)))
-            .min_by_key(|&(_, d)| FloatOrd(d))
+            .enumerate()
+            .filter_map(|(i, o)| o.collides_with(ray).map(|d| (i, None, d)));
+        let meshes = self.meshes.iter().enumerate().filter_map(|(i, m)| {
+            let sides = self.materials[m.material].sides;
+            m.collides_with(ray, sides)
+                .map(|(face, d)| (self.objects.len() + i, Some(face), d))
+        });
+        let (i, face, dist) = objects.chain(meshes).min_by_key(|&(_, _, d)| FloatOrd(d))?;
+        Some((i, self.hit(i, face), dist))
+    }
+
+    /// Finds the object or mesh seen through the center of a pixel, returning
+    /// its index. Meshes are numbered after the objects.
+    pub fn object_at(&self, x: u32, y: u32) -> Option<usize> {
+        let ray = self.make_camera_ray(x as f32 + 0.5, y as f32 + 0.5);
+        let (i, _, _) = self.closest_collision(ray, &Counters::default())?;
+        Some(i)
+    }
+
+    /// Returns the object with the given index, or the triangle of the mesh
+    /// with it.
+    fn hit(&self, i: usize, face: Option<usize>) -> Hit<'_> {
+        match face {
+            Some(face) => {
+                let mesh = &self.meshes[i - self.objects.len()];
+                Hit::Face(mesh.triangle(face, self.materials[mesh.material]))
+            }
+            None => Hit::Object(&self.objects[i]),
+        }
     }
 
-    fn light_on<R: Renderable>(&self, obj: &R, pos: Vector3<f32>) -> Rgb<f32> {
-        let norm = obj.normal_at(pos);
-        self.lights
+    /// Returns the light falling on a point, given the normal on the side of
+    /// the surface being lit, from both the lights and the given emitters.
+    fn light_on(
+        &self,
+        pos: Vector3<f32>,
+        norm: Vector3<f32>,
+        emitters: &[Emitter],
+        stats: &Counters,
+    ) -> Rgb<f32> {
+        let lights = self
+            .lights
+            .iter()
+            .filter_map(|l| self.one_light(l, pos, norm, stats));
+        let emitters = emitters
             .iter()
-            .filter_map(|l| self.one_light(l, obj, pos, norm))
-            .fold(BLACK, add_colors)
+            .flat_map(|e| emitter::samples(e, pos, self.light_samples))
+            .filter_map(|l| self.one_light(&l, pos, norm, stats));
+        lights.chain(emitters).fold(BLACK, add_colors)
     }
-    fn one_light<L, R>(
+    fn one_light<L: Light>(
         &self,
         light: &L,
-        obj: &R,
         pos: Vector3<f32>,
         norm: Vector3<f32>,
-    ) -> Option<Rgb<f32>>
-    where
-        L: Light,
-        R: Renderable,
-    {
+        stats: &Counters,
+    ) -> Option<Rgb<f32>> {
         let intensity = light.intensity_at(pos);
         if intensity < MIN_INTENSITY {
             None
         } else {
-            let (dir, dist) = light.direction_from(pos);
-            let ray = Ray {
-                origin: pos + 0.0001 * norm,
-                direction: dir,
-            };
-            let collision_dist = self
-                .closest_collision(ray)
-                .map(|(_, d)| d)
-                .unwrap_or(f32::INFINITY);
+            let (dir, _) = light.direction_from(pos);
+            let c = norm.angle(dir).cos();
+            if c < 0.0 {
+                return None;
+            }
 
             // Check for shadow.
-            if collision_dist >= dist {
-                let c = norm.angle(dir).cos();
-                if c >= 0.0 {
-                    Some(scale_color(light.color(), c * intensity))
-                } else {
-                    None
-                }
-            } else {
+            let transmittance = self.light_transmittance(light, pos, norm, stats);
+            if transmittance == BLACK {
                 None
+            } else {
+                let color = mul_colors(light.color(), transmittance);
+                Some(scale_color(color, c * intensity))
+            }
+        }
+    }
+
+    /// Returns the fraction of each channel of an emitter's light that
+    /// reaches the given position, averaged over the points it's sampled at.
+    pub(crate) fn emitter_transmittance(
+        &self,
+        emitter: &Emitter,
+        pos: Vector3<f32>,
+        norm: Vector3<f32>,
+        stats: &Counters,
+    ) -> Rgb<f32> {
+        let samples = emitter::samples(emitter, pos, self.light_samples);
+        // Summed as vectors, since adding colors clamps them.
+        let total = samples
+            .iter()
+            .map(|l| vector_from_rgb(self.light_transmittance(l, pos, norm, stats)))
+            .fold(Vector3::new(0.0, 0.0, 0.0), |a, b| a + b);
+        rgb_from_vector(total / samples.len().max(1) as f32)
+    }
+
+    /// Returns the fraction of each channel of the light that reaches the
+    /// given position. A shadow ray is cast towards the light, and continues
+    /// through transmissive objects, which tint the light; any other object
+    /// blocks it. So do more than `max_collisions` transmissive surfaces.
+    pub(crate) fn light_transmittance<L: Light>(
+        &self,
+        light: &L,
+        pos: Vector3<f32>,
+        norm: Vector3<f32>,
+        stats: &Counters,
+    ) -> Rgb<f32> {
+        let (dir, dist) = light.direction_from(pos);
+        let mut ray = Ray::leaving(pos, norm, dir);
+        let mut transmittance = WHITE;
+        // How far the ray has gone, and where it entered the objects with an
+        // attenuation distance that it's inside.
+        let mut travelled = 0.0;
+        let mut entered = Vec::new();
+
+        for _ in 0..=self.max_collisions {
+            // Each stretch of the ray between transmissive surfaces is cast
+            // separately.
+            Counters::add(&stats.shadow_rays, 1);
+            let (obj, d) = match self.closest_collision(ray, stats) {
+                Some((_, obj, d)) if travelled + d < dist => (obj, d),
+                _ => return transmittance,
+            };
+            let hit = ray.project(d);
+            travelled += d;
+            let normal = obj.normal_at(hit);
+            let front = normal.dot(dir) <= 0.0;
+            let material = obj.material().side(front);
+            let t = material.transmissive;
+            if t == BLACK {
+                return BLACK;
+            }
+
+            match material.attenuation_distance {
+                Some(_) if front => entered.push(travelled),
+                Some(scale) => {
+                    // A ray that started inside the object entered it at the
+                    // start.
+                    let inside = travelled - entered.pop().unwrap_or(0.0);
+                    let absorbed = Rgb {
+                        data: [
+                            t.data[0].powf(inside / scale),
+                            t.data[1].powf(inside / scale),
+                            t.data[2].powf(inside / scale),
+                        ],
+                    };
+                    transmittance = mul_colors(transmittance, absorbed);
+                }
+                None => transmittance = mul_colors(transmittance, t),
+            }
+            ray = Ray::leaving(hit, normal, dir);
+        }
+        BLACK
+    }
+}
+
+/// What a ray hit: one of the scene's objects, or a triangle of one of its
+/// meshes.
+#[derive(Debug)]
+pub(crate) enum Hit<'a> {
+    Object(&'a DynamicRenderable),
+    Face(Triangle),
+}
+
+impl<'a> Renderable for Hit<'a> {
+    fn collides_with(&self, ray: Ray) -> Option<f32> {
+        match *self {
+            Hit::Object(obj) => obj.collides_with(ray),
+            Hit::Face(ref tri) => tri.collides_with(ray),
+        }
+    }
+
+    fn material(&self) -> Material {
+        match *self {
+            Hit::Object(obj) => obj.material(),
+            Hit::Face(ref tri) => tri.material(),
+        }
+    }
+
+    fn normal_at(&self, pos: Vector3<f32>) -> Vector3<f32> {
+        match *self {
+            Hit::Object(obj) => obj.normal_at(pos),
+            Hit::Face(ref tri) => tri.normal_at(pos),
+        }
+    }
+}
+
+/// Refracts a unit direction through a surface with the given unit normal and
+/// index of refraction, returning `None` on total internal reflection.
+///
+/// The normal may point either way; if the direction is on the same side as
+/// it, the ray is taken to be leaving the object.
+fn refract(dir: Vector3<f32>, norm: Vector3<f32>, ior: f32) -> Option<Vector3<f32>> {
+    let (norm, eta) = if dir.dot(norm) > 0.0 {
+        (-norm, ior)
+    } else {
+        (norm, ior.recip())
+    };
+    let cos_i = -dir.dot(norm);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 {
+        None
+    } else {
+        Some(eta * dir + (eta * cos_i - k.sqrt()) * norm)
+    }
+}
+
+/// Returns the offset within a pixel of one of its samples. A single sample
+/// is at the pixel's corner; otherwise, they're spread over the pixel.
+fn sample_offset(i: usize, samples: usize) -> (f32, f32) {
+    if samples <= 1 {
+        return (0.0, 0.0);
+    }
+    spread(i, samples)
+}
+
+/// Returns the `i`th of `n` points spread over the unit square: evenly
+/// horizontally, and by the golden ratio vertically, so that no two share a
+/// row or column.
+fn spread(i: usize, n: usize) -> (f32, f32) {
+    let x = (i as f32 + 0.5) / n as f32;
+    let y = (0.5 + i as f32 * 0.618_034).fract();
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::fs;
+
+    use cgmath::{InnerSpace, Vector3};
+
+    use ray::Ray;
+    use renderable::Renderable;
+    use scene::Scene;
+    use stats::Counters;
+    use trace::packet::{Packet, LANES};
+    use trace::{emitter, emitters, spread};
+
+    #[test]
+    fn render_stats() {
+        // Only the bottom-right pixel's camera ray, which goes through its
+        // corner, hits the sphere; the rest hit the plane behind it.
+        let scene = |material: &str| -> Scene {
+            format!(
+                "film_resolution 2 2\ncamera 0 0 0 0 0 1 0 1 0 45\nplane 0 0 10 0 0 -1\n\
+                 {}\nsphere 0 0 5 1\ndirectional_light 1 1 1 0 0 1\n",
+                material
+            )
+            .parse()
+            .unwrap()
+        };
+
+        // Each hit casts a shadow ray, and tests it against both objects.
+        let (_, stats) = scene("").render_with_stats();
+        assert_eq!(stats.camera_rays, 4);
+        assert_eq!(stats.shadow_rays, 4);
+        assert_eq!(stats.secondary_rays, 0);
+        assert_eq!(stats.intersection_tests, 16);
+
+        // A glass sphere refracts the camera ray into and out of it, to the
+        // plane. Shadow rays from inside it and from behind it continue
+        // through one and two of its surfaces.
+        let (_, stats) = scene("material 0 0 0 1 1 1 0 0 0 1 1 1 1 1").render_with_stats();
+        assert_eq!(stats.camera_rays, 4);
+        assert_eq!(stats.secondary_rays, 2);
+        assert_eq!(stats.shadow_rays, 3 + 1 + 2 + 3);
+    }
+
+    #[test]
+    fn transparent_background() {
+        // Only the bottom-right pixel's camera ray, which goes through its
+        // corner, hits the glass sphere, and passes through it to nothing.
+        let scene: Scene = "film_resolution 2 2\ncamera 0 0 0 0 0 1 0 1 0 45\n\
+                            transparent_background\nambient_light 1 1 1\n\
+                            material 1 0 0 0 0 0 0 0 0 1 .5 .5 .5 1\nsphere 0 0 5 1\n"
+            .parse()
+            .unwrap();
+        let image = scene.render();
+        for &i in &[0, 1, 2] {
+            assert_eq!(image.pixels()[i], [0.0, 0.0, 0.0, 0.0]);
+        }
+
+        // The sphere's front and back each let half the coverage behind them
+        // through, so the pixel is three-quarters covered. Its color is
+        // un-premultiplied, so it isn't darkened by the ray that missed.
+        assert_eq!(image.pixels()[3], [1.0, 0.0, 0.0, 0.75]);
+    }
+
+    #[test]
+    fn tinted_shadows() {
+        let scene = |extra: &str| -> Scene {
+            format!(
+                "material 1 1 1 1 1 1 0 0 0 1 .5 .25 0 1\n{}\nsphere 0 2 0 1\n\
+                 directional_light 1 1 1 0 -1 0\n",
+                extra
+            )
+            .parse()
+            .unwrap()
+        };
+        let transmittance = |scene: &Scene, pos| {
+            let light = &scene.lights[0];
+            let norm = Vector3::new(0.0, 1.0, 0.0);
+            scene
+                .light_transmittance(light, pos, norm, &Counters::default())
+                .data
+        };
+        let below = Vector3::new(0.0, 0.0, 0.0);
+        let beside = Vector3::new(2.0, 0.0, 0.0);
+
+        // The light is scaled by the transmissive color at each surface.
+        let glass = scene("");
+        assert_eq!(transmittance(&glass, below), [0.25, 0.0625, 0.0]);
+        assert_eq!(transmittance(&glass, beside), [1.0, 1.0, 1.0]);
+
+        // Or by the distance travelled through the sphere, which is 2.
+        let thick = scene("attenuation_distance 1");
+        let t = transmittance(&thick, below);
+        assert!((t[0] - 0.25).abs() < 1e-4 && (t[1] - 0.0625).abs() < 1e-4 && t[2] == 0.0);
+        let thin = scene("attenuation_distance 4");
+        let t = transmittance(&thin, below);
+        assert!((t[0] - 0.5f32.sqrt()).abs() < 1e-4 && (t[1] - 0.5).abs() < 1e-4);
+
+        // Opaque objects still block the light.
+        let opaque = scene("material 1 1 1 1 1 1 0 0 0 1 0 0 0 1");
+        assert_eq!(transmittance(&opaque, below), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn emissive_objects() {
+        let light_on = |input: &str| {
+            let scene: Scene = format!(
+                "light_samples 64\nmaterial 0 0 0 0 0 0 0 0 0 1 0 0 0 1\n{}",
+                input
+            )
+            .parse()
+            .unwrap();
+            let (pos, norm) = (Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+            let emitters = emitters(&scene);
+            scene
+                .light_on(pos, norm, &emitters, &Counters::default())
+                .data
+        };
+        let assert_near = |actual: f32, expected: f32| {
+            assert!(
+                (actual - expected).abs() < 0.05 * expected,
+                "{} isn't near {}",
+                actual,
+                expected
+            );
+        };
+
+        // A unit square, 2 above the point.
+        let square = "emissive 1 .5 0\nvertex -.5 2 -.5\nvertex .5 2 -.5\nvertex .5 2 .5\n\
+                      vertex -.5 2 .5\ntriangle 0 1 2\ntriangle 0 2 3\n";
+        let lit = light_on(square);
+        assert_near(lit[0], 0.2308);
+        assert_near(lit[1], 0.1154);
+        assert_eq!(lit[2], 0.0);
+
+        // Half of it hidden behind an opaque triangle.
+        let half = light_on(&format!(
+            "{}material 0 0 0 0 0 0 0 0 0 1 0 0 0 1\nvertex 0 1 -10\nvertex 0 1 10\n\
+             vertex -10 1 0\ntriangle 4 5 6\n",
+            square
+        ));
+        assert_near(half[0], 0.1154);
+
+        // The same square split into uneven triangles is still one emitter,
+        // sampled as often as a single triangle, and gives the same light.
+        let split = "emissive 1 .5 0\nvertex -.5 2 -.5\nvertex .5 2 -.5\nvertex .5 2 .5\n\
+                     vertex -.5 2 .5\nvertex .5 2 0\ntriangle 0 1 4\ntriangle 0 4 2\n\
+                     triangle 0 2 3\n";
+        let scene: Scene = split.parse().unwrap();
+        let emitters = emitters(&scene);
+        assert_eq!(emitters.len(), 1);
+        let pos = Vector3::new(0.0, 0.0, 0.0);
+        assert_eq!(emitter::samples(&emitters[0], pos, 64).len(), 64);
+        assert_near(light_on(split)[0], 0.2308);
+
+        // Twice as bright.
+        let bright = light_on(&square.replace("emissive 1 .5 0", "emissive 1 .5 0 2"));
+        assert_near(bright[1], 0.2308);
+
+        // A sphere, 4 away, which lights the point as much as a point light
+        // in its center with the intensity of its cross-section.
+        let sphere = light_on("emissive 1 1 1\nsphere 0 4 0 .5\n");
+        assert_near(sphere[0], 0.25 * PI / 16.0);
+
+        // Planes don't light anything.
+        assert_eq!(light_on("emissive 1 1 1\nplane 0 4 0 0 1 0\n"), [0.0; 3]);
+    }
+
+    #[test]
+    fn packets_match_scalar() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "scn") {
+                continue;
+            }
+            let mut scene = Scene::from_file(&path).unwrap();
+            scene.height = (scene.height * 30 / scene.width).max(1);
+            scene.width = 30;
+            scene.samples = 2;
+            assert!(
+                scene.render().pixels() == scene.render_scalar().pixels(),
+                "{} renders differently in packets",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn picking() {
+        // A sphere in front of a plane, and two meshes sharing a material.
+        let mut scene: Scene = format!(
+            "film_resolution 40 30\nplane 0 0 10 0 0 -1\nsphere 0 0 5 1\n\
+             mesh_transform -3 0 5 1\nmesh {0}/scenes/meshes/octahedron.ply\n\
+             mesh_transform 3 0 5 1\nmesh {0}/scenes/meshes/octahedron.ply\n",
+            env!("CARGO_MANIFEST_DIR")
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(scene.object_at(20, 15), Some(1));
+        assert_eq!(scene.object_at(1, 1), Some(0));
+        assert_eq!(scene.object_at(11, 15), Some(2));
+        assert_eq!(scene.object_at(29, 15), Some(3));
+
+        // Changing one mesh's material leaves the other's alone.
+        scene.material_mut(3).unwrap().phong = 7.0;
+        assert_eq!(scene.materials.len(), 2);
+        assert_ne!(scene.materials[scene.meshes[0].material].phong, 7.0);
+        assert_eq!(scene.materials[scene.meshes[1].material].phong, 7.0);
+        scene.material_mut(1).unwrap().phong = 3.0;
+        assert_eq!(scene.objects[1].material().phong, 3.0);
+        assert!(scene.material_mut(4).is_none());
+    }
+
+    #[test]
+    fn packet_collisions() {
+        // Single- and double-sided planes, spheres and triangles, a mesh, and
+        // two triangles sharing an edge.
+        let scene: Scene = format!(
+            "plane 0 -2 0 0 1 0\nsphere 0 0 3 1\nsphere 2 1 -3 .5\nvertex -1 -1 2\n\
+             vertex 1 -1 2\nvertex 0 1 2\nvertex 0 -1 4\ntriangle 0 1 2\ntriangle 1 3 2\n\
+             material 1 1 1 1 1 1 0 0 0 1 0 0 0 1\nsingle_sided\nplane 0 3 0 0 -1 0\n\
+             sphere -2 0 -3 1\nvertex 3 0 0\nvertex 3 1 1\nvertex 3 -1 1\n\
+             triangle 4 5 6\nmesh {}/scenes/meshes/octahedron.ply\n",
+            env!("CARGO_MANIFEST_DIR")
+        )
+        .parse()
+        .unwrap();
+        let stats = Counters::default();
+
+        // Rays in all directions, from inside and outside the objects, and
+        // along the shared edge and through the shared vertex.
+        let mut rays = Vec::new();
+        for &origin in &[
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 3.0),
+            Vector3::new(0.3, -0.2, -5.0),
+        ] {
+            for i in 0..256 {
+                let (u, v) = spread(i, 256);
+                let (z, phi) = (1.0 - 2.0 * u, 2.0 * PI * v);
+                let r = (1.0 - z * z).sqrt();
+                let direction = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+                rays.push(Ray { origin, direction });
+            }
+        }
+        let edge = Vector3::new(0.5, 0.0, 3.0);
+        rays.push(Ray {
+            origin: Vector3::new(0.5, 0.0, 0.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        });
+        rays.push(Ray {
+            origin: Vector3::new(0.0, 0.0, -1.0),
+            direction: (edge - Vector3::new(0.0, 0.0, -1.0)).normalize(),
+        });
+        rays.push(Ray {
+            origin: Vector3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(0.0, 1.0, 2.0),
+        });
+
+        // Every packet size, including partly filled ones.
+        for len in 1..=LANES {
+            for rays in rays.chunks(len) {
+                let collisions = scene.closest_collisions(&Packet::new(rays), &stats);
+                for (lane, &ray) in rays.iter().enumerate() {
+                    let scalar = scene.closest_collision(ray, &stats);
+                    let expected = scalar.map(|(i, hit, d)| (i, hit, d.to_bits()));
+                    let actual =
+                        collisions[lane].map(|(i, face, d)| (i, scene.hit(i, face), d.to_bits()));
+                    assert_eq!(
+                        format!("{:?}", actual),
+                        format!("{:?}", expected),
+                        "{:?}",
+                        ray
+                    );
+                }
+                for collision in &collisions[rays.len()..] {
+                    assert!(collision.is_none());
+                }
             }
         }
     }
@@ -0,0 +1,257 @@
+//! Finding what camera rays hit, a packet of them at a time.
+//!
+//! Neighbouring camera rays mostly hit the same objects, so they're tested
+//! against each object together. A packet stores its rays as a structure of
+//! arrays, with a lane for each ray, and each step of an intersection test is
+//! a loop over the lanes. Each lane does exactly the arithmetic testing its
+//! ray on its own would, so the results are identical.
+//!
+//! Triangle tests look up the vertices' components along the axes of each
+//! ray's `Shear`. Neighbouring camera rays almost always share those axes, so
+//! a packet looks them up once for all its lanes; a packet whose rays don't
+//! share them has its triangle tests done a ray at a time instead.
+
+// Loops over the lanes index several arrays at once.
+#![allow(clippy::needless_range_loop)]
+
+use cgmath::Vector3;
+use float_ord::FloatOrd;
+
+use material::Sides;
+use ray::Ray;
+use renderable::{intersect_triangle, DynamicRenderable, Shear};
+use scene::Scene;
+use stats::Counters;
+
+/// The number of rays in a packet.
+pub(crate) const LANES: usize = 4;
+
+/// A value for each lane of a packet.
+type Lanes<T> = [T; LANES];
+
+/// Which lanes' rays hit something, and how far away.
+type Hits = (Lanes<bool>, Lanes<f32>);
+
+/// The closest collision of each lane's ray, as the index of the object or
+/// mesh, the index of the mesh's triangle, and the distance to it.
+pub(crate) type Collisions = Lanes<Option<(usize, Option<usize>, f32)>>;
+
+/// A packet of up to `LANES` rays. Unused lanes hold copies of the first ray,
+/// and their results are ignored.
+pub(crate) struct Packet {
+    /// The number of lanes in use.
+    len: usize,
+
+    /// The rays, for the rare lanes whose triangle tests are redone in
+    /// double precision.
+    rays: Lanes<Ray>,
+
+    /// The components of the rays' origins.
+    origin: [Lanes<f32>; 3],
+
+    /// The components of the rays' directions.
+    direction: [Lanes<f32>; 3],
+
+    /// The axes of the rays' `Shear`s, if they're the same for every ray.
+    axes: Option<[usize; 3]>,
+
+    /// The factors of each ray's `Shear`.
+    s: [Lanes<f32>; 3],
+}
+
+impl Packet {
+    /// Makes a packet of between 1 and `LANES` rays.
+    pub fn new(rays: &[Ray]) -> Packet {
+        assert!(!rays.is_empty() && rays.len() <= LANES);
+        let mut packet = Packet {
+            len: rays.len(),
+            rays: [rays[0]; LANES],
+            origin: [[0.0; LANES]; 3],
+            direction: [[0.0; LANES]; 3],
+            axes: None,
+            s: [[0.0; LANES]; 3],
+        };
+        let mut axes = Vec::with_capacity(LANES);
+        for i in 0..LANES {
+            let ray = *rays.get(i).unwrap_or(&rays[0]);
+            let shear = Shear::new(ray.direction);
+            packet.rays[i] = ray;
+            for axis in 0..3 {
+                packet.origin[axis][i] = ray.origin[axis];
+                packet.direction[axis][i] = ray.direction[axis];
+            }
+            axes.push([shear.kx, shear.ky, shear.kz]);
+            packet.s[0][i] = shear.sx;
+            packet.s[1][i] = shear.sy;
+            packet.s[2][i] = shear.sz;
+        }
+        if axes.iter().all(|&a| a == axes[0]) {
+            packet.axes = Some(axes[0]);
+        }
+        packet
+    }
+
+    /// Returns the dot product of a vector with each ray's direction.
+    fn dot_direction(&self, v: Vector3<f32>) -> Lanes<f32> {
+        let [dx, dy, dz] = self.direction;
+        let mut dot = [0.0; LANES];
+        for i in 0..LANES {
+            dot[i] = v.x * dx[i] + v.y * dy[i] + v.z * dz[i];
+        }
+        dot
+    }
+
+    /// Tests the rays against a plane, as `Plane::collides_with` does.
+    fn plane(&self, point: Vector3<f32>, normal: Vector3<f32>, sides: Sides) -> Hits {
+        let [ox, oy, oz] = self.origin;
+        let denom = self.dot_direction(normal);
+        let (mut hit, mut dist) = ([false; LANES], [0.0; LANES]);
+        for i in 0..LANES {
+            let numer = normal.x * (point.x - ox[i])
+                + normal.y * (point.y - oy[i])
+                + normal.z * (point.z - oz[i]);
+            dist[i] = numer / denom[i];
+            let facing = sides == Sides::Both || denom[i] < 0.0 || denom[i].is_nan();
+            hit[i] = facing && dist[i] >= 0.0 && dist[i].is_finite();
+        }
+        (hit, dist)
+    }
+
+    /// Tests the rays against a sphere, as `Sphere::collides_with` does.
+    fn sphere(&self, center: Vector3<f32>, radius: f32, sides: Sides) -> Hits {
+        let ([ox, oy, oz], [dx, dy, dz]) = (self.origin, self.direction);
+        let (mut hit, mut dist) = ([false; LANES], [0.0; LANES]);
+        for i in 0..LANES {
+            let (ocx, ocy, ocz) = (ox[i] - center.x, oy[i] - center.y, oz[i] - center.z);
+            let a = dx[i] * dx[i] + dy[i] * dy[i] + dz[i] * dz[i];
+            let half_b = dx[i] * ocx + dy[i] * ocy + dz[i] * ocz;
+            let c = (ocx * ocx + ocy * ocy + ocz * ocz) - radius * radius;
+            let k = half_b / a;
+            let (cx, cy, cz) = (ocx - k * dx[i], ocy - k * dy[i], ocz - k * dz[i]);
+            let delta = a * (radius * radius - (cx * cx + cy * cy + cz * cz));
+
+            let q = -half_b - delta.sqrt().copysign(half_b);
+            let (t0, t1) = (c / q, q / a);
+            let (near, far) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            let inside = far > 0.0 && sides == Sides::Both;
+            hit[i] = (delta >= 0.0 || delta.is_nan()) && (near > 0.0 || inside);
+            dist[i] = if near > 0.0 { near } else { far };
+        }
+        (hit, dist)
+    }
+
+    /// Tests the rays against a triangle, as `Triangle::collides_with` does.
+    fn triangle(
+        &self,
+        vertices: (Vector3<f32>, Vector3<f32>, Vector3<f32>),
+        normal: Vector3<f32>,
+        sides: Sides,
+    ) -> Hits {
+        let facing = if sides == Sides::Front {
+            let dot = self.dot_direction(normal);
+            let mut facing = [false; LANES];
+            for i in 0..LANES {
+                facing[i] = dot[i] < 0.0 || dot[i].is_nan();
+            }
+            facing
+        } else {
+            [true; LANES]
+        };
+
+        let (mut hit, mut dist) = ([false; LANES], [0.0; LANES]);
+        let mut redo = [true; LANES];
+        if let Some([kx, ky, kz]) = self.axes {
+            let (v0, v1, v2): ([f32; 3], [f32; 3], [f32; 3]) =
+                (vertices.0.into(), vertices.1.into(), vertices.2.into());
+            let (ox, oy, oz) = (self.origin[kx], self.origin[ky], self.origin[kz]);
+            let [sx, sy, sz] = self.s;
+            for i in 0..LANES {
+                let (ax, ay, az) = (v0[kx] - ox[i], v0[ky] - oy[i], v0[kz] - oz[i]);
+                let (bx, by, bz) = (v1[kx] - ox[i], v1[ky] - oy[i], v1[kz] - oz[i]);
+                let (cx, cy, cz) = (v2[kx] - ox[i], v2[ky] - oy[i], v2[kz] - oz[i]);
+                let (ax, ay) = (ax - sx[i] * az, ay - sy[i] * az);
+                let (bx, by) = (bx - sx[i] * bz, by - sy[i] * bz);
+                let (cx, cy) = (cx - sx[i] * cz, cy - sy[i] * cz);
+
+                let u = cx * by - cy * bx;
+                let v = ax * cy - ay * cx;
+                let w = bx * ay - by * ax;
+                redo[i] = u == 0.0 || v == 0.0 || w == 0.0;
+                let outside = (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0);
+                let det = u + v + w;
+                let t = u * sz[i] * az + v * sz[i] * bz + w * sz[i] * cz;
+                dist[i] = t / det;
+                hit[i] = facing[i] && !outside && det != 0.0 && dist[i] >= 0.0;
+            }
+        }
+
+        // Rays on or very near an edge are tested again on their own, to get
+        // the signs of the edge functions right, as are all the rays of a
+        // packet whose rays' shears have different axes.
+        for i in 0..self.len {
+            if redo[i] && facing[i] {
+                let d = intersect_triangle(vertices, self.rays[i]);
+                hit[i] = d.is_some();
+                dist[i] = d.unwrap_or(0.0);
+            }
+        }
+        (hit, dist)
+    }
+}
+
+impl Scene {
+    /// Finds the closest object or mesh each ray in the packet collides with,
+    /// just as `closest_collision` would for each ray on its own.
+    pub(crate) fn closest_collisions(&self, packet: &Packet, stats: &Counters) -> Collisions {
+        let faces = self.meshes.iter().map(|m| m.faces.len()).sum::<usize>();
+        Counters::add(
+            &stats.intersection_tests,
+            packet.len * (self.objects.len() + faces),
+        );
+
+        // As with `min_by_key`, the first of several equally close
+        // collisions is kept.
+        let mut closest: Collisions = [None; LANES];
+        let mut update = |i: usize, face: Option<usize>, (hit, dist): Hits| {
+            for lane in 0..packet.len {
+                let closer = match closest[lane] {
+                    Some((_, _, d)) => FloatOrd(dist[lane]) < FloatOrd(d),
+                    None => true,
+                };
+                if hit[lane] && closer {
+                    closest[lane] = Some((i, face, dist[lane]));
+                }
+            }
+        };
+
+        for (i, obj) in self.objects.iter().enumerate() {
+            let hits = match *obj {
+                DynamicRenderable::Plane(ref p) => {
+                    packet.plane(p.point, p.normal, p.material.sides)
+                }
+                DynamicRenderable::Sphere(ref s) => {
+                    packet.sphere(s.position, s.radius, s.material.sides)
+                }
+                DynamicRenderable::Triangle(ref t) => {
+                    packet.triangle(t.vertices, t.normal, t.material.sides)
+                }
+            };
+            update(i, None, hits);
+        }
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            let sides = self.materials[mesh.material].sides;
+            for face in 0..mesh.faces.len() {
+                // Finding the normal takes time, and is only needed for
+                // single-sided meshes.
+                let normal = if sides == Sides::Front {
+                    mesh.normal(face)
+                } else {
+                    Vector3::new(0.0, 0.0, 0.0)
+                };
+                let hits = packet.triangle(mesh.vertices(face), normal, sides);
+                update(self.objects.len() + i, Some(face), hits);
+            }
+        }
+        closest
+    }
+}
@@ -4,7 +4,9 @@ use ray::Ray;
 use scene::Scene;
 
 impl Scene {
-    pub(crate) fn make_camera_ray(&self, x: u32, y: u32) -> Ray {
+    /// Makes the camera ray through a point on the image, in pixels from its
+    /// top-left corner.
+    pub(crate) fn make_camera_ray(&self, x: f32, y: f32) -> Ray {
         let x = self.x_to_ray(x);
         let y = self.y_to_ray(y);
         Ray {
@@ -22,13 +24,13 @@ impl Scene {
         (self.width as f32) / (self.height as f32)
     }
 
-    fn x_to_ray(&self, x: u32) -> f32 {
-        let x = (2.0 * x as f32 / self.width as f32) - 1.0;
+    fn x_to_ray(&self, x: f32) -> f32 {
+        let x = (2.0 * x / self.width as f32) - 1.0;
         return x * self.camera_half_angle_tan * self.aspect_ratio();
     }
 
-    fn y_to_ray(&self, y: u32) -> f32 {
-        let y = 1.0 - (2.0 * y as f32 / self.height as f32);
+    fn y_to_ray(&self, y: f32) -> f32 {
+        let y = 1.0 - (2.0 * y / self.height as f32);
         y * self.camera_half_angle_tan
     }
 }
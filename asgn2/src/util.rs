@@ -1,5 +1,7 @@
 //! Simple utilities.
 
+use std::time::Duration;
+
 use cgmath::num_traits::clamp;
 use cgmath::Vector3;
 use float_ord::FloatOrd;
@@ -10,6 +12,11 @@ pub const BLACK: Rgb<f32> = Rgb {
     data: [0.0, 0.0, 0.0],
 };
 
+/// The color white.
+pub const WHITE: Rgb<f32> = Rgb {
+    data: [1.0, 1.0, 1.0],
+};
+
 /// Blends two colors, returning a new one.
 pub fn add_colors(a: Rgb<f32>, b: Rgb<f32>) -> Rgb<f32> {
     normalize_color_down(Rgb {
@@ -56,6 +63,11 @@ pub fn normalize_color_down(c: Rgb<f32>) -> Rgb<f32> {
     rgb_from_vector(c / n)
 }
 
+/// Converts a duration to a whole number of milliseconds.
+pub fn millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + u64::from(d.subsec_millis())
+}
+
 /// Multiplies a scalar by a color.
 pub fn scale_color(c: Rgb<f32>, n: f32) -> Rgb<f32> {
     Rgb {
@@ -63,6 +75,36 @@ pub fn scale_color(c: Rgb<f32>, n: f32) -> Rgb<f32> {
     }
 }
 
+/// Serializes colors as `[r, g, b]`, for use with `#[serde(with)]`.
+pub(crate) mod serde_rgb {
+    use image::Rgb;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(c: &Rgb<f32>, s: S) -> Result<S::Ok, S::Error> {
+        c.data.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Rgb<f32>, D::Error> {
+        Ok(Rgb {
+            data: Deserialize::deserialize(d)?,
+        })
+    }
+}
+
+/// Serializes vectors as `[x, y, z]`, for use with `#[serde(with)]`.
+pub(crate) mod serde_vector {
+    use cgmath::Vector3;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Vector3<f32>, s: S) -> Result<S::Ok, S::Error> {
+        [v.x, v.y, v.z].serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vector3<f32>, D::Error> {
+        <[f32; 3]>::deserialize(d).map(Vector3::from)
+    }
+}
+
 /// Converts a vector to a RGB color.
 pub fn rgb_from_vector(v: Vector3<f32>) -> Rgb<f32> {
     Rgb {
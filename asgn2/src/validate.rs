@@ -0,0 +1,378 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use cgmath::{InnerSpace, Vector3};
+
+use light::DynamicLight;
+use renderable::DynamicRenderable;
+use scene::Scene;
+use util::BLACK;
+
+/// How serious an issue with a scene is.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// The scene can be rendered, but probably not as intended.
+    Warning,
+
+    /// The scene can't be rendered correctly.
+    Error,
+}
+
+/// A problem with a scene, found by `Scene::validate`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Issue {
+    /// The image's width or height is zero, so there's nothing to render.
+    ZeroResolution,
+
+    /// The camera direction has zero length, or isn't finite.
+    ZeroCameraDirection,
+
+    /// The camera's up vector has zero length, or isn't finite.
+    ZeroCameraUp,
+
+    /// The camera's up vector is parallel to its direction, so the camera's
+    /// orientation is undefined.
+    CameraUpParallel,
+
+    /// The light with the given index has a direction with zero length.
+    ZeroLightDirection(usize),
+
+    /// The directional light with the given index has a direction that isn't
+    /// normalized, which scales its intensity.
+    UnnormalizedLightDirection(usize),
+
+    /// The point or spot light with the given index has a negative
+    /// attenuation coefficient, or only zero ones, so its intensity can be
+    /// infinite or negative.
+    BadAttenuation(usize),
+
+    /// The plane with the given object index has a normal with zero length.
+    ZeroPlaneNormal(usize),
+
+    /// The plane with the given object index has a normal that isn't
+    /// normalized, which scales its shading.
+    UnnormalizedPlaneNormal(usize),
+
+    /// The plane with the given object index has an emissive material. It
+    /// glows, but since it's infinite, it doesn't light the rest of the scene.
+    EmissivePlane(usize),
+
+    /// The sphere with the given object index has a negative radius.
+    NegativeRadius(usize),
+
+    /// The triangle with the given object index has zero area, so it can
+    /// never be hit. Only scenes in structured formats have triangle objects;
+    /// triangles from scene files are faces of meshes, so they're reported
+    /// as `DegenerateFaces`.
+    DegenerateTriangle(usize),
+
+    /// The mesh with the given index refers to a material or vertex that
+    /// doesn't exist, or has a different number of normals or colors than
+    /// vertices.
+    InvalidMesh(usize),
+
+    /// The mesh with the given index has faces with zero area, which can
+    /// never be hit.
+    DegenerateFaces(usize),
+}
+
+impl Issue {
+    /// Returns how serious the issue is.
+    pub fn severity(self) -> Severity {
+        match self {
+            Issue::ZeroResolution
+            | Issue::ZeroCameraDirection
+            | Issue::ZeroCameraUp
+            | Issue::CameraUpParallel
+            | Issue::ZeroLightDirection(_)
+            | Issue::BadAttenuation(_)
+            | Issue::ZeroPlaneNormal(_)
+            | Issue::InvalidMesh(_) => Severity::Error,
+            Issue::UnnormalizedLightDirection(_)
+            | Issue::UnnormalizedPlaneNormal(_)
+            | Issue::EmissivePlane(_)
+            | Issue::NegativeRadius(_)
+            | Issue::DegenerateTriangle(_)
+            | Issue::DegenerateFaces(_) => Severity::Warning,
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let severity = match self.severity() {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(fmt, "{}: ", severity)?;
+        match *self {
+            Issue::ZeroResolution => write!(fmt, "the image's width or height is zero"),
+            Issue::ZeroCameraDirection => write!(fmt, "the camera direction has zero length"),
+            Issue::ZeroCameraUp => write!(fmt, "the camera up vector has zero length"),
+            Issue::CameraUpParallel => {
+                write!(fmt, "the camera up vector is parallel to its direction")
+            }
+            Issue::ZeroLightDirection(i) => {
+                write!(fmt, "light {}: direction has zero length", i)
+            }
+            Issue::UnnormalizedLightDirection(i) => {
+                write!(fmt, "light {}: direction is not normalized", i)
+            }
+            Issue::BadAttenuation(i) => write!(
+                fmt,
+                "light {}: attenuation coefficients are negative or all zero",
+                i
+            ),
+            Issue::ZeroPlaneNormal(i) => write!(fmt, "object {}: plane normal has zero length", i),
+            Issue::UnnormalizedPlaneNormal(i) => {
+                write!(fmt, "object {}: plane normal is not normalized", i)
+            }
+            Issue::EmissivePlane(i) => {
+                write!(fmt, "object {}: emissive planes don't light the scene", i)
+            }
+            Issue::NegativeRadius(i) => write!(fmt, "object {}: sphere radius is negative", i),
+            Issue::DegenerateTriangle(i) => {
+                write!(fmt, "object {}: triangle has zero area", i)
+            }
+            Issue::InvalidMesh(i) => write!(
+                fmt,
+                "mesh {}: refers to a material or vertex that doesn't exist, or has the wrong \
+                 number of normals or colors",
+                i
+            ),
+            Issue::DegenerateFaces(i) => write!(fmt, "mesh {}: some faces have zero area", i),
+        }
+    }
+}
+
+impl Scene {
+    /// Checks the scene for values that can't be rendered, or probably weren't
+    /// intended. Lights, objects and meshes are referred to by their index in
+    /// `lights`, `objects` and `meshes`.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        if self.width == 0 || self.height == 0 {
+            issues.push(Issue::ZeroResolution);
+        }
+        let direction_ok = is_nonzero(self.camera_direction);
+        let up_ok = is_nonzero(self.camera_up);
+        if !direction_ok {
+            issues.push(Issue::ZeroCameraDirection);
+        }
+        if !up_ok {
+            issues.push(Issue::ZeroCameraUp);
+        }
+        let (d, u) = (self.camera_direction, self.camera_up);
+        if direction_ok
+            && up_ok
+            && u.cross(d).magnitude2() <= 1e-12 * u.magnitude2() * d.magnitude2()
+        {
+            issues.push(Issue::CameraUpParallel);
+        }
+
+        for (i, light) in self.lights.iter().enumerate() {
+            let attenuation = match *light {
+                DynamicLight::Point(ref l) => Some(l.attenuation),
+                DynamicLight::Spot(ref l) => Some(l.attenuation),
+                DynamicLight::Directional(_) => None,
+            };
+            if let Some(a) = attenuation {
+                let coefficients = [a.constant, a.linear, a.quadratic];
+                if coefficients.iter().any(|&c| c < 0.0) || coefficients.iter().all(|&c| c == 0.0) {
+                    issues.push(Issue::BadAttenuation(i));
+                }
+            }
+
+            let direction = match *light {
+                DynamicLight::Directional(ref l) => l.direction,
+                DynamicLight::Spot(ref l) => l.direction,
+                DynamicLight::Point(_) => continue,
+            };
+            if !is_nonzero(direction) {
+                issues.push(Issue::ZeroLightDirection(i));
+            } else if let DynamicLight::Directional(_) = *light {
+                if !is_normalized(direction) {
+                    issues.push(Issue::UnnormalizedLightDirection(i));
+                }
+            }
+        }
+
+        for (i, obj) in self.objects.iter().enumerate() {
+            match *obj {
+                DynamicRenderable::Plane(ref p) => {
+                    if !is_nonzero(p.normal) {
+                        issues.push(Issue::ZeroPlaneNormal(i));
+                    } else if !is_normalized(p.normal) {
+                        issues.push(Issue::UnnormalizedPlaneNormal(i));
+                    }
+                    if p.material.emissive != BLACK {
+                        issues.push(Issue::EmissivePlane(i));
+                    }
+                }
+                DynamicRenderable::Sphere(ref s) => {
+                    if s.radius < 0.0 {
+                        issues.push(Issue::NegativeRadius(i));
+                    }
+                }
+                DynamicRenderable::Triangle(ref t) => {
+                    let (v1, v2, v3) = t.vertices;
+                    if !is_nonzero((v2 - v1).cross(v3 - v1)) {
+                        issues.push(Issue::DegenerateTriangle(i));
+                    }
+                }
+            }
+        }
+
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            let n = mesh.positions.len();
+            let valid = mesh.material < self.materials.len()
+                && mesh
+                    .normals
+                    .as_ref()
+                    .is_none_or(|normals| normals.len() == n)
+                && mesh.colors.as_ref().is_none_or(|colors| colors.len() == n)
+                && mesh.faces.iter().flatten().all(|&v| (v as usize) < n);
+            if !valid {
+                issues.push(Issue::InvalidMesh(i));
+                continue;
+            }
+            let p = &mesh.positions;
+            let degenerate = mesh.faces.iter().any(|&[a, b, c]| {
+                let (a, b, c) = (p[a as usize], p[b as usize], p[c as usize]);
+                !is_nonzero((b - a).cross(c - a))
+            });
+            if degenerate {
+                issues.push(Issue::DegenerateFaces(i));
+            }
+        }
+
+        issues
+    }
+}
+
+/// Returns whether a vector has a finite, non-zero length.
+fn is_nonzero(v: Vector3<f32>) -> bool {
+    let len = v.magnitude2();
+    len > 0.0 && len.is_finite()
+}
+
+/// Returns whether a vector has a length of 1, within rounding error.
+fn is_normalized(v: Vector3<f32>) -> bool {
+    (v.magnitude2() - 1.0).abs() <= 1e-4
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::Vector3;
+
+    use light::{Attenuation, Directional, DynamicLight, Point};
+    use material::Material;
+    use mesh::Mesh;
+    use renderable::{DynamicRenderable, Plane, Sphere, Triangle};
+    use scene::Scene;
+    use util::{BLACK, WHITE};
+    use validate::{Issue, Severity};
+
+    #[test]
+    fn validate() {
+        let v = Vector3::new;
+        let scene = Scene {
+            camera_direction: v(0.0, 2.0, 0.0),
+            width: 0,
+            lights: vec![
+                DynamicLight::Directional(Directional {
+                    color: BLACK,
+                    direction: v(0.0, 0.0, 0.0),
+                    intensity: 1.0,
+                }),
+                DynamicLight::Point(Point {
+                    color: BLACK,
+                    position: v(0.0, 0.0, 0.0),
+                    intensity: 1.0,
+                    attenuation: Attenuation {
+                        constant: 0.0,
+                        linear: 0.0,
+                        quadratic: 0.0,
+                    },
+                }),
+            ],
+            objects: vec![
+                DynamicRenderable::Plane(Plane {
+                    point: v(0.0, 0.0, 0.0),
+                    normal: v(0.0, 2.0, 0.0),
+                    material: Material {
+                        emissive: WHITE,
+                        ..Material::default()
+                    },
+                }),
+                DynamicRenderable::Sphere(Sphere {
+                    position: v(0.0, 0.0, 0.0),
+                    radius: -1.0,
+                    material: Default::default(),
+                }),
+                DynamicRenderable::Triangle(Triangle {
+                    vertices: (v(0.0, 0.0, 0.0), v(1.0, 1.0, 1.0), v(2.0, 2.0, 2.0)),
+                    normal: v(0.0, 0.0, 0.0),
+                    material: Default::default(),
+                }),
+            ],
+            meshes: vec![
+                Mesh {
+                    positions: Arc::new(vec![v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(0.0, 1.0, 0.0)]),
+                    faces: Arc::new(vec![[0, 1, 2]]),
+                    material: 1,
+                    ..Mesh::default()
+                },
+                Mesh {
+                    positions: Arc::new(vec![v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(0.0, 1.0, 0.0)]),
+                    faces: Arc::new(vec![[0, 1, 2], [0, 1, 1]]),
+                    ..Mesh::default()
+                },
+            ],
+            materials: vec![Material::default()],
+            ..Scene::default()
+        };
+        let issues = scene.validate();
+        assert_eq!(
+            issues,
+            vec![
+                Issue::ZeroResolution,
+                Issue::CameraUpParallel,
+                Issue::ZeroLightDirection(0),
+                Issue::BadAttenuation(1),
+                Issue::UnnormalizedPlaneNormal(0),
+                Issue::EmissivePlane(0),
+                Issue::NegativeRadius(1),
+                Issue::DegenerateTriangle(2),
+                Issue::InvalidMesh(0),
+                Issue::DegenerateFaces(1),
+            ]
+        );
+        assert_eq!(issues[0].severity(), Severity::Error);
+        assert_eq!(issues[1].severity(), Severity::Error);
+        assert_eq!(
+            issues[4].to_string(),
+            "warning: object 0: plane normal is not normalized"
+        );
+        assert!(Scene::default().validate().is_empty());
+        let flat = Scene {
+            height: 0,
+            ..Scene::default()
+        };
+        assert_eq!(flat.validate(), vec![Issue::ZeroResolution]);
+    }
+
+    #[test]
+    fn degenerate_triangles() {
+        let input = "sphere 0 0 5 1\nvertex 0 0 0\nvertex 1 1 1\nvertex 2 2 2\nvertex 0 1 0\n\
+                     triangle 0 1 3\ntriangle 0 1 2\n";
+        let issues = input.parse::<Scene>().unwrap().validate();
+        assert_eq!(issues, vec![Issue::DegenerateFaces(0)]);
+        assert_eq!(
+            issues[0].to_string(),
+            "warning: mesh 0: some faces have zero area"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Renders every scene in `scenes` at a low resolution and compares the
+//! result against the reference images in `tests/golden`.
+//!
+//! Two images match if at most `MAX_DIFFERENT` of their pixels have a channel
+//! that differs by more than `TOLERANCE`, which allows for small differences
+//! in floating-point rounding without letting a visible change through. When
+//! a scene doesn't match, the render and a diff image, in which differing
+//! pixels are red and the rest are a dimmed copy of the reference, are
+//! written to `target/tmp/golden`.
+//!
+//! After an intended change to the output, run the tests with `BLESS=1` to
+//! replace the reference images with the new renders.
+
+extern crate image;
+extern crate raytracer;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{Rgb, RgbImage};
+use raytracer::Scene;
+
+/// The width scenes are rendered at. The height is scaled to keep the scene's
+/// aspect ratio.
+const WIDTH: u32 = 80;
+
+/// The largest difference in a channel, out of 255, for which two pixels are
+/// considered the same.
+const TOLERANCE: u8 = 3;
+
+/// The largest fraction of pixels that may differ.
+const MAX_DIFFERENT: f32 = 0.002;
+
+#[test]
+fn golden() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let bless = env::var_os("BLESS").is_some_and(|v| !v.is_empty() && v != "0");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+
+    let mut scenes = fs::read_dir(root.join("scenes"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "scn"))
+        .collect::<Vec<_>>();
+    scenes.sort();
+    assert!(!scenes.is_empty());
+
+    let mut failures = Vec::new();
+    for path in scenes {
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let image = render(&path);
+        let reference = root
+            .join("tests")
+            .join("golden")
+            .join(format!("{}.png", name));
+
+        if bless {
+            fs::create_dir_all(reference.parent().unwrap()).unwrap();
+            image.save(&reference).unwrap();
+            continue;
+        }
+
+        let expected = match image::open(&reference) {
+            Ok(expected) => expected.to_rgb(),
+            Err(err) => {
+                failures.push(format!(
+                    "{}: couldn't read {}: {}",
+                    name,
+                    reference.display(),
+                    err
+                ));
+                continue;
+            }
+        };
+        if let Err(msg) = compare(&image, &expected, &out_dir, &name) {
+            failures.push(format!("{}: {}", name, msg));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} scene(s) didn't match their reference images; if the changes are \
+         intended, rerun with BLESS=1\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+/// Renders a scene at `WIDTH` pixels wide.
+fn render(path: &Path) -> RgbImage {
+    let mut scene = Scene::from_file(path).unwrap_or_else(|errs| panic!("{}", errs));
+    let height = (WIDTH as f32 * scene.height as f32 / scene.width as f32).round();
+    scene.width = WIDTH;
+    scene.height = (height as u32).max(1);
+    scene.render().to_rgb8()
+}
+
+/// Compares a render against its reference image. If they don't match, the
+/// render and a diff image are written to `out_dir`.
+fn compare(
+    actual: &RgbImage,
+    expected: &RgbImage,
+    out_dir: &Path,
+    name: &str,
+) -> Result<(), String> {
+    if actual.dimensions() != expected.dimensions() {
+        let path = save(out_dir, name, "actual", actual);
+        return Err(format!(
+            "the render is {:?}, but the reference is {:?}; wrote the render to {}",
+            actual.dimensions(),
+            expected.dimensions(),
+            path.display()
+        ));
+    }
+
+    let mut different = 0;
+    let mut worst = 0;
+    let diff = RgbImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let (a, e) = (actual[(x, y)].data, expected[(x, y)].data);
+        let delta = (0..3)
+            .map(|i| (a[i] as i16 - e[i] as i16).unsigned_abs() as u8)
+            .max()
+            .unwrap();
+        worst = worst.max(delta);
+        if delta > TOLERANCE {
+            different += 1;
+            Rgb([255, 0, 0])
+        } else {
+            let luma = (e[0] as u32 * 3 + e[1] as u32 * 6 + e[2] as u32) / 10;
+            let v = (luma / 3) as u8;
+            Rgb([v, v, v])
+        }
+    });
+
+    let total = actual.width() * actual.height();
+    if different as f32 > MAX_DIFFERENT * total as f32 {
+        save(out_dir, name, "actual", actual);
+        let path = save(out_dir, name, "diff", &diff);
+        Err(format!(
+            "{} of {} pixels differ, by up to {}; wrote a diff to {}",
+            different,
+            total,
+            worst,
+            path.display()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Saves an image as `out_dir/name.kind.png`, returning its path.
+fn save(out_dir: &Path, name: &str, kind: &str, image: &RgbImage) -> PathBuf {
+    fs::create_dir_all(out_dir).unwrap();
+    let path = out_dir.join(format!("{}.{}.png", name, kind));
+    image.save(&path).unwrap();
+    path
+}
@@ -145,7 +145,8 @@ impl<T: StructOpt> StructOpt for Args<T> {
                     .help("Turns off message output.")
                     .short("q")
                     .long("quiet"),
-            ).arg(
+            )
+            .arg(
                 Arg::with_name("verbose")
                     .takes_value(false)
                     .multiple(true)